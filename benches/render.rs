@@ -0,0 +1,67 @@
+//! Rendering benchmarks, run with `cargo bench`. The font database holds only the bundled font,
+//! so the timings do not depend on the fonts installed on the machine.
+
+use cosmic_text::{FontSystem, SwashCache};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use fonts::{render_text, RenderOptions};
+use std::hint::black_box;
+
+fn font_system() -> FontSystem {
+    let mut db = fontdb::Database::new();
+    db.load_font_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/FiraMono-Medium.ttf"
+    ))
+    .expect("the bundled font to load");
+    FontSystem::new_with_locale_and_db("en-US".to_string(), db)
+}
+
+/// Renders a repetitive text with the outline of each unique glyph converted once and reused,
+/// and with it converted again for every occurrence, which shows what the reuse saves
+fn repeated_glyphs(c: &mut Criterion) {
+    let mut font_system = font_system();
+    let mut swash_cache = SwashCache::new();
+    let text = "The quick brown fox jumps over the lazy dog. ".repeat(50);
+
+    let mut group = c.benchmark_group("repeated glyphs");
+    group.throughput(Throughput::Elements(text.chars().count() as u64));
+    for (name, reuse_outlines) in [("reused", true), ("converted each time", false)] {
+        let opts = RenderOptions::default()
+            .font_family("Fira Mono")
+            .reuse_outlines(reuse_outlines);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                render_text(black_box(&text), &opts, &mut font_system, &mut swash_cache).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Renders a paragraph of prose, wrapped into lines. Glyphs are placed in parallel with the
+/// rayon feature, which comparing against a serial build measures:
+///
+/// ```text
+/// cargo bench --bench render -- paragraph --save-baseline serial
+/// cargo bench --bench render --features rayon -- paragraph --baseline serial
+/// ```
+fn paragraph(c: &mut Criterion) {
+    let mut font_system = font_system();
+    let mut swash_cache = SwashCache::new();
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .wrap_width(600.0);
+    let text = "Engraved lettering is cut line by line, so every outline has to be closed, \
+                free of overlaps and in a known winding before it reaches the machine. "
+        .repeat(20);
+
+    let mut group = c.benchmark_group("paragraph");
+    group.throughput(Throughput::Elements(text.chars().count() as u64));
+    group.bench_function("render", |b| {
+        b.iter(|| render_text(black_box(&text), &opts, &mut font_system, &mut swash_cache).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, repeated_glyphs, paragraph);
+criterion_main!(benches);
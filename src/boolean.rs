@@ -0,0 +1,57 @@
+use crate::{Point, Primitive, Shape};
+use i_overlay::core::fill_rule::FillRule;
+use i_overlay::float::simplify::SimplifyShape;
+
+/// Merges overlapping contours, such as the connected letters of a script font, into the outline
+/// of the region they cover together, so no seams are left inside it once extruded.
+///
+/// The contours must be flattened into lines first. Holes are kept wherever no other contour
+/// covers them, as the regions are filled with the nonzero rule and holes wind against their
+/// outer contour in fonts.
+///
+/// The regions no longer belong to single characters, so every shape of the result has an empty
+/// text and the index of its region, with the outer contour first and its holes after it. Shapes
+/// without primitives are kept as they are, after the regions.
+pub fn union_shapes(shapes: Vec<Shape>) -> Vec<Shape> {
+    let (empty, shapes): (Vec<Shape>, Vec<Shape>) = shapes
+        .into_iter()
+        .partition(|shape| shape.primitives.is_empty());
+
+    let contours: Vec<Vec<[f64; 2]>> = shapes
+        .iter()
+        .flat_map(|shape| shape.contours())
+        .map(|contour| {
+            contour
+                .iter()
+                .map(|primitive| {
+                    let start = primitive.start();
+                    [f64::from(start.0), f64::from(start.1)]
+                })
+                .collect()
+        })
+        .collect();
+
+    contours
+        .simplify_shape(FillRule::NonZero)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, region)| {
+            region
+                .into_iter()
+                .enumerate()
+                .map(move |(contour, points)| {
+                    let points: Vec<Point> = points
+                        .into_iter()
+                        .map(|[x, y]| Point(x as f32, y as f32))
+                        .collect();
+                    let primitives = points
+                        .iter()
+                        .zip(points.iter().cycle().skip(1))
+                        .map(|(start, end)| Primitive::Line(start.clone(), end.clone()))
+                        .collect();
+                    Shape::new(primitives, String::new(), index, contour)
+                })
+        })
+        .chain(empty)
+        .collect()
+}
@@ -0,0 +1,62 @@
+use crate::{Point, Primitive, Shape};
+
+/// Drops zero-length primitives, whose points all lie within `epsilon` of their start, which CAD
+/// kernels reject as degenerate edges.
+///
+/// The primitive following a dropped one is snapped onto the end of the previous primitive, so
+/// contours stay connected. Curves whose end points meet but whose control points do not are
+/// loops and are kept, as are closes, which mark the end of their contour whatever their length.
+pub fn clean_shape(shape: Shape, epsilon: f32) -> Shape {
+    shape.map_primitives(|primitives| {
+        let mut cleaned: Vec<Primitive> = vec![];
+        let mut last_end: Option<Point> = None;
+        for primitive in primitives {
+            let start = primitive.start().clone();
+            if is_degenerate(&primitive, epsilon) {
+                // Keep chaining from the previous end, unless this starts a new contour
+                if !last_end
+                    .as_ref()
+                    .is_some_and(|end| near(end, &start, epsilon))
+                {
+                    last_end = Some(start);
+                }
+                continue;
+            }
+
+            let primitive = match last_end.take() {
+                Some(end) if end != start && near(&end, &start, epsilon) => {
+                    snap_start(primitive, end)
+                }
+                _ => primitive,
+            };
+            last_end = Some(primitive.end().clone());
+            cleaned.push(primitive);
+        }
+        cleaned
+    })
+}
+
+fn near(a: &Point, b: &Point, epsilon: f32) -> bool {
+    (a.0 - b.0).abs() <= epsilon && (a.1 - b.1).abs() <= epsilon
+}
+
+fn is_degenerate(primitive: &Primitive, epsilon: f32) -> bool {
+    let start = primitive.start();
+    match primitive {
+        Primitive::Line(_, end) => near(start, end, epsilon),
+        Primitive::Close(..) => false,
+        Primitive::Quadratic(_, c, end) => near(start, c, epsilon) && near(start, end, epsilon),
+        Primitive::Bezier(_, c0, c1, end) => {
+            near(start, c0, epsilon) && near(start, c1, epsilon) && near(start, end, epsilon)
+        }
+    }
+}
+
+fn snap_start(primitive: Primitive, start: Point) -> Primitive {
+    match primitive {
+        Primitive::Line(_, end) => Primitive::Line(start, end),
+        Primitive::Close(_, end) => Primitive::Close(start, end),
+        Primitive::Quadratic(_, c, end) => Primitive::Quadratic(start, c, end),
+        Primitive::Bezier(_, c0, c1, end) => Primitive::Bezier(start, c0, c1, end),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{Point, Primitive, Shape, Units};
+
+/// Number of line segments each curve is split into when writing polylines
+const CURVE_SEGMENTS: usize = 16;
+
+/// Renders the shapes as an ASCII DXF document.
+///
+/// Each contour becomes a closed LWPOLYLINE with its curves flattened, unless `splines` is set, in
+/// which case lines are written as LINE entities and curves as exact SPLINE entities. `units` is
+/// recorded as the drawing units so importers scale the drawing correctly. Coordinates are
+/// written with a decimal comma instead of a point if `decimal_comma` is set, for importers
+/// parsing numbers in a locale that uses one.
+pub fn to_dxf(
+    shapes: &[Shape],
+    splines: bool,
+    units: Option<Units>,
+    decimal_comma: bool,
+) -> String {
+    let mut dxf = Dxf {
+        decimal_comma,
+        ..Dxf::default()
+    };
+    dxf.pair(0, "SECTION");
+    dxf.pair(2, "HEADER");
+    dxf.pair(9, "$ACADVER");
+    dxf.pair(1, "AC1015");
+    dxf.pair(9, "$INSUNITS");
+    // DXF has no code for points, those drawings are left unitless
+    dxf.pair(
+        70,
+        match units {
+            Some(Units::Inches) => 1,
+            Some(Units::Millimetres) => 4,
+            Some(Units::Points) | None => 0,
+        },
+    );
+    dxf.pair(0, "ENDSEC");
+    dxf.pair(0, "SECTION");
+    dxf.pair(2, "ENTITIES");
+
+    for contour in shapes.iter().flat_map(|shape| shape.contours()) {
+        if splines {
+            for primitive in contour {
+                dxf.primitive(primitive);
+            }
+        } else {
+            dxf.polyline(contour);
+        }
+    }
+
+    dxf.pair(0, "ENDSEC");
+    dxf.pair(0, "EOF");
+    dxf.out
+}
+
+#[derive(Default)]
+struct Dxf {
+    out: String,
+    decimal_comma: bool,
+}
+
+impl Dxf {
+    fn pair(&mut self, code: u16, value: impl std::fmt::Display) {
+        self.out.push_str(&format!("{code}\n{value}\n"));
+    }
+
+    fn point(&mut self, code: u16, point: &Point) {
+        self.real(code, point.0);
+        self.real(code + 10, point.1);
+    }
+
+    fn real(&mut self, code: u16, value: f32) {
+        if self.decimal_comma {
+            self.pair(code, value.to_string().replace('.', ","));
+        } else {
+            self.pair(code, value);
+        }
+    }
+
+    fn polyline(&mut self, contour: &[Primitive]) {
+        let mut points: Vec<Point> = contour
+            .iter()
+            .flat_map(|primitive| {
+                let mut points = primitive.sample(CURVE_SEGMENTS);
+                points.pop();
+                points
+            })
+            .collect();
+        if let Some(last) = contour.last() {
+            if last.end() != contour[0].start() {
+                points.push(last.end().clone());
+            }
+        }
+
+        self.pair(0, "LWPOLYLINE");
+        self.pair(8, 0);
+        self.pair(90, points.len());
+        self.pair(70, 1);
+        for point in points.iter() {
+            self.point(10, point);
+        }
+    }
+
+    fn primitive(&mut self, primitive: &Primitive) {
+        let (degree, control_points) = match primitive {
+            Primitive::Close(p0, p1) if p0 == p1 => return,
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => {
+                self.pair(0, "LINE");
+                self.pair(8, 0);
+                self.point(10, p0);
+                self.point(11, p1);
+                return;
+            }
+            Primitive::Quadratic(p0, c, p1) => (2, vec![p0, c, p1]),
+            Primitive::Bezier(p0, c0, c1, p1) => (3, vec![p0, c0, c1, p1]),
+        };
+
+        // A single Bezier segment is a clamped B-spline with no interior knots
+        self.pair(0, "SPLINE");
+        self.pair(8, 0);
+        self.pair(70, 8);
+        self.pair(71, degree);
+        self.pair(72, 2 * control_points.len());
+        self.pair(73, control_points.len());
+        for knot in 0..2 * control_points.len() {
+            self.pair(40, if knot < control_points.len() { 0 } else { 1 });
+        }
+        for point in control_points {
+            self.point(10, point);
+            self.pair(30, 0);
+        }
+    }
+}
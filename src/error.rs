@@ -0,0 +1,91 @@
+use cosmic_text::Style;
+use thiserror::Error;
+
+/// Everything that can go wrong while turning text into geometry
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("font not found: {0}")]
+    FontNotFound(String),
+
+    #[error("{path} could not be parsed as a font")]
+    InvalidFont { path: String },
+
+    #[error("font index {index} is out of range, {path} contains {count} face(s)")]
+    FontIndexOutOfRange {
+        path: String,
+        index: usize,
+        count: usize,
+    },
+
+    #[error("{font} has no \"{axis}\" variation axis")]
+    UnknownVariationAxis { axis: String, font: String },
+
+    #[error("no outline is available for glyph {glyph_id}")]
+    NoOutline { glyph_id: u16 },
+
+    #[error("malformed outline for glyph {glyph_id}: {reason}")]
+    MalformedOutline { glyph_id: u16, reason: &'static str },
+
+    #[error("no glyph found for {0}")]
+    MissingGlyphs(String),
+
+    #[error("the geometry of {0:?} has an infinite or NaN coordinate")]
+    NonFiniteGeometry(String),
+
+    #[error("no renderable glyphs in the input")]
+    EmptyGeometry,
+
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+
+    #[error("cannot write to {path}: {reason}")]
+    OutputNotWritable { path: String, reason: String },
+
+    #[error("{path} is not a valid batch manifest: {source}")]
+    InvalidManifest {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("{failed} of {total} batch entries failed")]
+    BatchFailed { failed: usize, total: usize },
+
+    #[error("unable to serialize the output: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Problems that do not stop the rendering, collected in [`crate::Rendering::warnings`] for the
+/// caller to report
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum Warning {
+    #[error("no glyph found for {0}, the font's placeholder box is drawn instead")]
+    MissingGlyphs(String),
+
+    #[error("{font} has no face with weight {requested}, using weight {used} instead (a faux weight may result)")]
+    MissingWeight {
+        font: String,
+        requested: u16,
+        used: u16,
+    },
+
+    #[error("{font} has no {requested:?} face, the {used:?} face is used instead and will not be slanted")]
+    MissingStyle {
+        font: String,
+        requested: Style,
+        used: Style,
+    },
+
+    #[error("glyph {glyph_id} for {text:?} has no outline and is left out")]
+    NoOutline { glyph_id: u16, text: String },
+
+    #[error(
+        "the layers of color glyphs are merged into plain outlines, their colors are discarded"
+    )]
+    MergedColorLayers,
+
+    #[error("some curves needed more than {max_segments} lines to stay within the tolerance of {tolerance} and deviate further")]
+    SegmentsCapped { max_segments: usize, tolerance: f32 },
+}
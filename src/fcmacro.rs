@@ -0,0 +1,105 @@
+use crate::{Point, Primitive, Shape};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Builds the glyphs in the active document, or a new one, once the data below is defined
+const BUILDER: &str = r#"
+def edge(poles):
+    points = [App.Vector(x, y, 0) for x, y in poles]
+    if len(points) == 2:
+        return Part.LineSegment(*points).toShape()
+    degree = len(points) - 1
+    curve = Part.BSplineCurve()
+    curve.buildFromPolesMultsKnots(points, [degree + 1, degree + 1], [0.0, 1.0], False, degree)
+    return curve.toShape()
+
+
+doc = App.ActiveDocument or App.newDocument("Text")
+for text, stroke, contours in GLYPHS:
+    wires = [Part.Wire([edge(poles) for poles in contour]) for contour in contours]
+    if not stroke and all(wire.isClosed() for wire in wires):
+        shape = Part.makeFace(wires, "Part::FaceMakerBullseye")
+    else:
+        shape = Part.Compound(wires)
+    feature = doc.addObject("Part::Feature", "Glyph")
+    feature.Label = text or "Glyph"
+    feature.Shape = shape
+doc.recompute()
+"#;
+
+/// Renders the shapes as a FreeCAD macro building one `Part::Feature` per glyph.
+///
+/// Lines become `Part.LineSegment` edges and curves `Part.BSplineCurve` edges with the control
+/// points of the curve as poles, of degree 2 for quadratic and degree 3 for cubic curves, so the
+/// outline is reproduced exactly. The contours of a glyph are joined into a face with its holes
+/// cut out, unless they are strokes or open, in which case they are kept as wires. Coordinates
+/// are taken as millimetres, FreeCAD's unit of length.
+pub fn to_fcmacro(shapes: &[Shape]) -> String {
+    let mut glyphs: BTreeMap<usize, Vec<&Shape>> = BTreeMap::new();
+    for shape in shapes.iter().filter(|shape| !shape.primitives.is_empty()) {
+        glyphs.entry(shape.index).or_default().push(shape);
+    }
+
+    let mut out = String::from("# Text rendered by fonts, run it as a macro in FreeCAD\n");
+    out.push_str("import FreeCAD as App\nimport Part\n\n");
+    out.push_str("# Text, whether it is a stroke, and the edges of every contour as their poles\n");
+    out.push_str("GLYPHS = [\n");
+    for shapes in glyphs.values() {
+        let stroke = shapes.iter().any(|shape| shape.stroke);
+        writeln!(
+            out,
+            "    ({}, {}, [",
+            python_string(&shapes[0].character),
+            if stroke { "True" } else { "False" }
+        )
+        .expect("Writing to a String cannot fail");
+        for contour in shapes.iter().flat_map(|shape| shape.contours()) {
+            let edges: Vec<String> = contour
+                .iter()
+                // FreeCAD rejects zero-length edges, the wire is closed without them
+                .filter(|primitive| !matches!(primitive, Primitive::Close(p0, p1) if p0 == p1))
+                .map(edge)
+                .collect();
+            writeln!(out, "        [{}],", edges.join(", "))
+                .expect("Writing to a String cannot fail");
+        }
+        out.push_str("    ]),\n");
+    }
+    out.push_str("]\n");
+    out.push_str(BUILDER);
+    out
+}
+
+/// The poles of the primitive as a Python tuple of points
+fn edge(primitive: &Primitive) -> String {
+    let poles: Vec<&Point> = match primitive {
+        Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => vec![p0, p1],
+        Primitive::Quadratic(p0, c, p1) => vec![p0, c, p1],
+        Primitive::Bezier(p0, c0, c1, p1) => vec![p0, c0, c1, p1],
+    };
+    let poles: Vec<String> = poles
+        .iter()
+        .map(|point| format!("({}, {})", point.0, point.1))
+        .collect();
+    format!("({})", poles.join(", "))
+}
+
+/// Quotes the text as a Python string literal. Macros are read as UTF-8, so only quotes,
+/// backslashes and control characters need escaping
+fn python_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => {
+                write!(quoted, "\\u{:04x}", u32::from(c)).expect("Writing to a String cannot fail")
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,124 @@
+use crate::{Point, Primitive, Shape};
+
+/// Number of lines a single curve is flattened into at most when no other cap is given
+pub const DEFAULT_MAX_SEGMENTS: usize = 256;
+
+/// Replaces every curve of the shape with lines deviating at most `tolerance` from the curve.
+///
+/// End points are kept exactly, so closed contours stay closed. A curve is never split into more
+/// than `max_segments` lines, rounded down to a power of two, so a tiny tolerance cannot exhaust
+/// memory. Curves reaching the cap are flattened only as far as it allows, which the returned flag
+/// reports.
+pub fn flatten_shape(shape: Shape, tolerance: f32, max_segments: usize) -> (Shape, bool) {
+    let max_depth = max_segments.max(1).ilog2();
+    let mut capped = false;
+    let shape = shape.map_primitives(|primitives| {
+        let mut flattened = vec![];
+        for primitive in primitives {
+            capped |= flatten(primitive, tolerance, max_depth, &mut flattened);
+        }
+        flattened
+    });
+    (shape, capped)
+}
+
+/// Replaces every curve whose control points lie less than `tolerance` from its chord with a line
+/// between its end points. The curve deviates from the line by less than that as well, since it
+/// lies within the hull of its control points
+pub fn straighten_shape(shape: Shape, tolerance: f32) -> Shape {
+    shape.map_primitives(|primitives| {
+        primitives
+            .into_iter()
+            .map(|primitive| match primitive {
+                Primitive::Quadratic(..) | Primitive::Bezier(..)
+                    if flatness(&primitive) < tolerance =>
+                {
+                    Primitive::Line(primitive.start().clone(), primitive.end().clone())
+                }
+                primitive => primitive,
+            })
+            .collect()
+    })
+}
+
+/// Flattens the primitive into `out`, splitting it at most `depth` times in a row. Returns whether
+/// that limit was reached before the lines were within `tolerance`
+fn flatten(primitive: Primitive, tolerance: f32, depth: u32, out: &mut Vec<Primitive>) -> bool {
+    if let Primitive::Line(..) | Primitive::Close(..) = primitive {
+        out.push(primitive);
+        return false;
+    }
+
+    let flat = flatness(&primitive) <= tolerance;
+    if flat || depth == 0 {
+        out.push(Primitive::Line(
+            primitive.start().clone(),
+            primitive.end().clone(),
+        ));
+        return !flat;
+    }
+
+    let (first, second) = split(&primitive);
+    let capped = flatten(first, tolerance, depth - 1, out);
+    flatten(second, tolerance, depth - 1, out) || capped
+}
+
+/// Largest distance between a control point and the chord, which bounds the distance between
+/// the curve and the chord since the curve lies within the hull of its control points
+fn flatness(primitive: &Primitive) -> f32 {
+    let controls = match primitive {
+        Primitive::Line(..) | Primitive::Close(..) => return 0.0,
+        Primitive::Quadratic(_, c, _) => vec![c],
+        Primitive::Bezier(_, c0, c1, _) => vec![c0, c1],
+    };
+    controls
+        .into_iter()
+        .map(|c| distance_to_segment(c, primitive.start(), primitive.end()))
+        .fold(0.0, f32::max)
+}
+
+pub(crate) fn distance_to_segment(point: &Point, start: &Point, end: &Point) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+fn midpoint(a: &Point, b: &Point) -> Point {
+    Point((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+/// Splits a curve in half using de Casteljau's algorithm
+fn split(primitive: &Primitive) -> (Primitive, Primitive) {
+    match primitive {
+        Primitive::Quadratic(p0, c, p1) => {
+            let (a, b) = (midpoint(p0, c), midpoint(c, p1));
+            let mid = midpoint(&a, &b);
+            (
+                Primitive::Quadratic(p0.clone(), a, mid.clone()),
+                Primitive::Quadratic(mid, b, p1.clone()),
+            )
+        }
+        Primitive::Bezier(p0, c0, c1, p1) => {
+            let (a, b, c) = (midpoint(p0, c0), midpoint(c0, c1), midpoint(c1, p1));
+            let (d, e) = (midpoint(&a, &b), midpoint(&b, &c));
+            let mid = midpoint(&d, &e);
+            (
+                Primitive::Bezier(p0.clone(), a, d, mid.clone()),
+                Primitive::Bezier(mid, e, c, p1.clone()),
+            )
+        }
+        Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => {
+            let mid = midpoint(p0, p1);
+            (
+                Primitive::Line(p0.clone(), mid.clone()),
+                Primitive::Line(mid, p1.clone()),
+            )
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::flatten::{flatten_shape, DEFAULT_MAX_SEGMENTS};
+use crate::{Point, Primitive, Shape};
+use std::fmt::Write;
+
+/// Flattening tolerance used when none is given, in output units
+pub const DEFAULT_TOLERANCE: f32 = 0.01;
+
+/// Renders the shapes as G-code tracing every contour with the tool lowered to `cut_z`.
+///
+/// Curves are flattened into lines deviating at most `tolerance` from them. The tool rapids to
+/// the start of each contour at `safe_z`, plunges, follows the contour at `feed` back to its start
+/// and lifts again. Coordinates are written as they are, so the output should be scaled into
+/// millimetres, e.g. with a target size.
+pub fn to_gcode(shapes: &[Shape], tolerance: f32, feed: f32, safe_z: f32, cut_z: f32) -> String {
+    let mut gcode = String::new();
+    gcode.push_str("G21 ; millimetres\n");
+    gcode.push_str("G90 ; absolute coordinates\n");
+    writeln!(gcode, "G0 Z{safe_z}").expect("Writing to a String cannot fail");
+
+    for shape in shapes {
+        let (shape, _) = flatten_shape(shape.clone(), tolerance, DEFAULT_MAX_SEGMENTS);
+        for contour in shape.contours() {
+            let Some(start) = contour.first().map(Primitive::start) else {
+                continue;
+            };
+            writeln!(gcode, "G0 X{} Y{}", start.0, start.1)
+                .expect("Writing to a String cannot fail");
+            writeln!(gcode, "G1 Z{cut_z} F{feed}").expect("Writing to a String cannot fail");
+            for primitive in contour {
+                if !matches!(primitive, Primitive::Close(p0, p1) if p0 == p1) {
+                    move_to(&mut gcode, primitive.end());
+                }
+            }
+            if contour.last().map(Primitive::end) != Some(start) {
+                move_to(&mut gcode, start);
+            }
+            writeln!(gcode, "G0 Z{safe_z}").expect("Writing to a String cannot fail");
+        }
+    }
+
+    gcode.push_str("M2\n");
+    gcode
+}
+
+/// Feed move to `point`, the feed rate set when plunging stays in effect
+fn move_to(gcode: &mut String, point: &Point) {
+    writeln!(gcode, "G1 X{} Y{}", point.0, point.1).expect("Writing to a String cannot fail");
+}
@@ -0,0 +1,486 @@
+use crate::Error;
+use cosmic_text::Transform;
+use serde::{Deserialize, Serialize};
+
+/// A point in the output coordinate space, Y grows upwards
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Point(pub f32, pub f32);
+
+impl Point {
+    /// Maps the point into the box spanned by `min_point` and `max_point`, scaled uniformly so the
+    /// longer side of the box becomes 0..1. Both axes share the same factor to keep the aspect ratio
+    pub(crate) fn map_scale(self, min_point: &Point, max_point: &Point) -> Self {
+        let range = f32::max(max_point.0 - min_point.0, max_point.1 - min_point.1);
+        let x = (self.0 - min_point.0) / range;
+        let y = (self.1 - min_point.1) / range;
+        Point(x, y)
+    }
+
+    /// Applies the affine `transform` to the point
+    pub(crate) fn transform(self, transform: &Transform) -> Self {
+        Point(
+            self.0 * transform.xx + self.1 * transform.yx + transform.x,
+            self.0 * transform.xy + self.1 * transform.yy + transform.y,
+        )
+    }
+
+    /// Truncates both coordinates to `digits` decimal places
+    pub(crate) fn round(self, digits: u32) -> Self {
+        Point(round(self.0, digits), round(self.1, digits))
+    }
+
+    /// Whether both coordinates are finite numbers, neither infinite nor NaN
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite() && self.1.is_finite()
+    }
+
+    /// Component-wise minimum of both points
+    pub fn min(&self, other: &Point) -> Point {
+        Point(f32::min(self.0, other.0), f32::min(self.1, other.1))
+    }
+
+    /// Component-wise maximum of both points
+    pub fn max(&self, other: &Point) -> Point {
+        Point(f32::max(self.0, other.0), f32::max(self.1, other.1))
+    }
+}
+
+/// Largest distance, along each axis, between the ends of a contour still considered closed
+pub const CLOSED_EPSILON: f32 = 1e-6;
+
+/// Truncates `value` to `digits` decimal places
+pub(crate) fn round(value: f32, digits: u32) -> f32 {
+    let factor = 10f32.powi(digits as i32);
+    (value * factor).trunc() / factor
+}
+
+/// A segment of a contour, from its first point to its last point
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Primitive {
+    /// A quadratic Bezier curve: start, control point, end
+    Quadratic(Point, Point, Point),
+    /// A cubic Bezier curve: start, two control points, end
+    Bezier(Point, Point, Point, Point),
+    /// A straight line: start, end
+    Line(Point, Point),
+    /// The segment closing a contour, a straight line back to its first point: start, end. Only
+    /// recorded when the closes of the outline are kept, and of zero length when the contour
+    /// already ends at its first point
+    Close(Point, Point),
+}
+
+impl Primitive {
+    /// The point the primitive starts at
+    pub fn start(&self) -> &Point {
+        match self {
+            Primitive::Quadratic(p, _, _) => p,
+            Primitive::Bezier(p, _, _, _) => p,
+            Primitive::Line(p, _) | Primitive::Close(p, _) => p,
+        }
+    }
+
+    /// The point the primitive ends at
+    pub fn end(&self) -> &Point {
+        match self {
+            Primitive::Quadratic(_, _, p) => p,
+            Primitive::Bezier(_, _, _, p) => p,
+            Primitive::Line(_, p) | Primitive::Close(_, p) => p,
+        }
+    }
+
+    /// Applies `f` to every point, including control points
+    pub fn map_points(self, f: impl Fn(Point) -> Point) -> Self {
+        match self {
+            Primitive::Quadratic(p1, p2, p3) => Primitive::Quadratic(f(p1), f(p2), f(p3)),
+            Primitive::Bezier(p1, p2, p3, p4) => Primitive::Bezier(f(p1), f(p2), f(p3), f(p4)),
+            Primitive::Line(p1, p2) => Primitive::Line(f(p1), f(p2)),
+            Primitive::Close(p1, p2) => Primitive::Close(f(p1), f(p2)),
+        }
+    }
+
+    /// The same primitive traced from its end to its start. A close becomes a line, as it no
+    /// longer leads back to the start of its contour
+    pub fn reverse(self) -> Self {
+        match self {
+            Primitive::Quadratic(p0, c, p1) => Primitive::Quadratic(p1, c, p0),
+            Primitive::Bezier(p0, c0, c1, p1) => Primitive::Bezier(p1, c1, c0, p0),
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => Primitive::Line(p1, p0),
+        }
+    }
+
+    /// Elevates a quadratic curve to the cubic Bezier tracing the exact same curve, other
+    /// primitives are returned unchanged
+    pub fn into_cubic(self) -> Self {
+        match self {
+            Primitive::Quadratic(p0, c, p1) => {
+                let c0 = Point(
+                    p0.0 + 2.0 / 3.0 * (c.0 - p0.0),
+                    p0.1 + 2.0 / 3.0 * (c.1 - p0.1),
+                );
+                let c1 = Point(
+                    p1.0 + 2.0 / 3.0 * (c.0 - p1.0),
+                    p1.1 + 2.0 / 3.0 * (c.1 - p1.1),
+                );
+                Primitive::Bezier(p0, c0, c1, p1)
+            }
+            other => other,
+        }
+    }
+
+    /// The end and control points of the primitive, in order
+    pub fn points(&self) -> Vec<&Point> {
+        match self {
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => vec![p0, p1],
+            Primitive::Quadratic(p0, c, p1) => vec![p0, c, p1],
+            Primitive::Bezier(p0, c0, c1, p1) => vec![p0, c0, c1, p1],
+        }
+    }
+
+    /// Evaluates the primitive at `t` in 0..=1
+    pub fn point_at(&self, t: f32) -> Point {
+        let u = 1.0 - t;
+        match self {
+            Primitive::Quadratic(p0, c, p1) => Point(
+                u * u * p0.0 + 2.0 * u * t * c.0 + t * t * p1.0,
+                u * u * p0.1 + 2.0 * u * t * c.1 + t * t * p1.1,
+            ),
+            Primitive::Bezier(p0, c0, c1, p1) => Point(
+                u * u * u * p0.0
+                    + 3.0 * u * u * t * c0.0
+                    + 3.0 * u * t * t * c1.0
+                    + t * t * t * p1.0,
+                u * u * u * p0.1
+                    + 3.0 * u * u * t * c0.1
+                    + 3.0 * u * t * t * c1.1
+                    + t * t * t * p1.1,
+            ),
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => {
+                Point(u * p0.0 + t * p1.0, u * p0.1 + t * p1.1)
+            }
+        }
+    }
+
+    /// Length of the path the primitive follows, exact for lines. Curves are integrated
+    /// numerically with Gauss-Legendre quadrature over their derivative
+    pub fn length(&self) -> f32 {
+        // Nodes and weights of five point Gauss-Legendre quadrature on -1..=1
+        const NODES: [(f32, f32); 5] = [
+            (0.0, 0.568_888_9),
+            (-0.538_469_3, 0.478_628_67),
+            (0.538_469_3, 0.478_628_67),
+            (-0.906_179_85, 0.236_926_88),
+            (0.906_179_85, 0.236_926_88),
+        ];
+        // Curves are split into pieces so sharp turns are integrated accurately as well
+        const PIECES: usize = 8;
+        match self {
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => (p1.0 - p0.0).hypot(p1.1 - p0.1),
+            _ => {
+                (0..PIECES)
+                    .flat_map(|piece| {
+                        NODES.iter().map(move |(node, weight)| {
+                            let t = (piece as f32 + (node + 1.0) / 2.0) / PIECES as f32;
+                            let Point(dx, dy) = self.derivative_at(t);
+                            weight * dx.hypot(dy)
+                        })
+                    })
+                    .sum::<f32>()
+                    / (2.0 * PIECES as f32)
+            }
+        }
+    }
+
+    /// Derivative of the primitive with respect to `t` at `t`
+    fn derivative_at(&self, t: f32) -> Point {
+        let u = 1.0 - t;
+        match self {
+            Primitive::Quadratic(p0, c, p1) => Point(
+                2.0 * (u * (c.0 - p0.0) + t * (p1.0 - c.0)),
+                2.0 * (u * (c.1 - p0.1) + t * (p1.1 - c.1)),
+            ),
+            Primitive::Bezier(p0, c0, c1, p1) => Point(
+                3.0 * (u * u * (c0.0 - p0.0) + 2.0 * u * t * (c1.0 - c0.0) + t * t * (p1.0 - c1.0)),
+                3.0 * (u * u * (c0.1 - p0.1) + 2.0 * u * t * (c1.1 - c0.1) + t * t * (p1.1 - c1.1)),
+            ),
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => Point(p1.0 - p0.0, p1.1 - p0.1),
+        }
+    }
+
+    /// Samples the primitive into points, including both end points. Curves are split into
+    /// `segments` uniform steps, lines are returned as their two end points
+    pub fn sample(&self, segments: usize) -> Vec<Point> {
+        match self {
+            Primitive::Line(p0, p1) | Primitive::Close(p0, p1) => vec![p0.clone(), p1.clone()],
+            _ => (0..=segments)
+                .map(|i| self.point_at(i as f32 / segments as f32))
+                .collect(),
+        }
+    }
+}
+
+/// Winding direction of a contour
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// Clockwise, the winding of outer contours in TrueType fonts
+    Cw,
+    /// Counter-clockwise, the winding of holes in TrueType fonts
+    Ccw,
+}
+
+/// The space a glyph takes up in the layout, as opposed to the extent of its outline. Lengths
+/// are in output units, scaled along with the geometry but not rotated
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutBox {
+    /// Pen position on the baseline the glyph starts at, transformed along with the geometry
+    pub pen: Point,
+    /// Distance the pen moves on to the next glyph
+    pub advance: f32,
+    /// Distance from the baseline to the top of the tallest glyphs of the font
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the deepest glyphs of the font, as a positive
+    /// length
+    pub descent: f32,
+}
+
+/// The box enclosing a glyph, including the control points of its curves
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+/// A single contour of a glyph
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Shape {
+    pub primitives: Vec<Primitive>,
+    /// The text of the cluster this contour belongs to
+    #[serde(rename = "char")]
+    pub character: String,
+    /// Index of the glyph within the laid out text, shared by every contour of the glyph
+    pub index: usize,
+    /// Index of the contour within its glyph
+    pub contour: usize,
+    /// Index of the laid out line the glyph is on, counting wrapped lines separately
+    #[serde(default)]
+    pub line: usize,
+    /// Byte range of the cluster in the text, once Unicode normalization is applied, from its
+    /// first byte to the byte after it. A ligature drawing several characters spans all of them,
+    /// and the glyphs of one cluster, such as a base and its marks, share the same range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_range: Option<(usize, usize)>,
+    /// Winding of the shape, filled in by [`Shape::annotate`] once the geometry is final
+    pub orientation: Option<Orientation>,
+    /// Whether every contour ends where it starts, filled in by [`Shape::annotate`]
+    #[serde(default)]
+    pub closed: bool,
+    /// Signed area enclosed by the shape, filled in by [`Shape::annotate`]. Negative when the
+    /// shape winds clockwise, see [`Orientation`]
+    #[serde(default)]
+    pub area: f32,
+    /// Length of the path along every primitive, in output units, only set when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<f32>,
+    /// Whether the contour is meant to be followed as an open wire, e.g. by a pen plotter or
+    /// engraver, instead of being filled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stroke: bool,
+    /// Pen position of a glyph without an outline, such as a space, so its place in the layout
+    /// is kept. Only set on shapes without primitives
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Point>,
+    /// Layout box of the glyph the contour belongs to, only set when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout_box: Option<LayoutBox>,
+    /// Box enclosing every contour of the glyph in the output coordinates, only set when
+    /// requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<Bounds>,
+}
+
+/// Fails with [`Error::NonFiniteGeometry`] naming the first shape with an infinite or NaN
+/// coordinate, which a malformed font or an extreme transformation can produce
+pub fn check_finite(shapes: &[Shape]) -> Result<(), Error> {
+    match shapes.iter().find(|shape| !shape.is_finite()) {
+        Some(shape) => Err(Error::NonFiniteGeometry(shape.character.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Returns the combined bounding box of all shapes, or `None` if there are no shapes with
+/// primitives
+pub fn bounding_box(shapes: &[Shape]) -> Option<(Point, Point)> {
+    shapes
+        .iter()
+        .filter_map(Shape::get_bb)
+        .reduce(|(min_p, max_p), (p0, p1)| (min_p.min(&p0), max_p.max(&p1)))
+}
+
+impl Shape {
+    /// Creates a shape whose derived fields, such as its orientation, are not computed yet
+    pub fn new(
+        primitives: Vec<Primitive>,
+        character: String,
+        index: usize,
+        contour: usize,
+    ) -> Self {
+        Self {
+            primitives,
+            character,
+            index,
+            contour,
+            line: 0,
+            source_range: None,
+            orientation: None,
+            closed: false,
+            area: 0.0,
+            length: None,
+            stroke: false,
+            origin: None,
+            layout_box: None,
+            bbox: None,
+        }
+    }
+
+    /// Replaces the primitives while keeping the rest of the shape
+    pub fn map_primitives(self, f: impl FnOnce(Vec<Primitive>) -> Vec<Primitive>) -> Self {
+        Self {
+            primitives: f(self.primitives),
+            ..self
+        }
+    }
+
+    /// The same shape with every contour traced from its end to its start, which flips the sign
+    /// of its signed area. Contours keep their order, and a contour ending in a close still does,
+    /// starting from the point the close started at instead
+    pub fn reverse(self) -> Self {
+        let mut reversed = Vec::with_capacity(self.primitives.len());
+        for contour in self.contours() {
+            match contour.split_last() {
+                Some((Primitive::Close(end, start), rest)) if !rest.is_empty() => {
+                    reversed.extend(rest.iter().rev().cloned().map(Primitive::reverse));
+                    reversed.push(Primitive::Close(start.clone(), end.clone()));
+                }
+                _ => reversed.extend(contour.iter().rev().cloned().map(Primitive::reverse)),
+            }
+        }
+        Self {
+            primitives: reversed,
+            ..self
+        }
+    }
+
+    /// Computes the fields derived from the final geometry
+    pub(crate) fn annotate(mut self) -> Self {
+        if self.primitives.is_empty() {
+            return self;
+        }
+        self.area = self.signed_area();
+        self.orientation = Some(if self.area < 0.0 {
+            Orientation::Cw
+        } else {
+            Orientation::Ccw
+        });
+        self.closed = self.is_closed();
+        self
+    }
+
+    /// Whether the last point of every contour lies within [`CLOSED_EPSILON`] of its first point
+    pub fn is_closed(&self) -> bool {
+        let contours = self.contours();
+        !contours.is_empty()
+            && contours.iter().all(|contour| {
+                let (start, end) = (contour[0].start(), contour[contour.len() - 1].end());
+                (start.0 - end.0).abs() <= CLOSED_EPSILON
+                    && (start.1 - end.1).abs() <= CLOSED_EPSILON
+            })
+    }
+
+    /// Length of the path along every primitive, see [`Primitive::length`]
+    pub fn length(&self) -> f32 {
+        self.primitives.iter().map(Primitive::length).sum()
+    }
+
+    /// Signed area enclosed by the contours, using the shoelace formula over the flattened
+    /// outline. Counter-clockwise contours count positively, as Y grows upwards
+    pub fn signed_area(&self) -> f32 {
+        self.contours()
+            .into_iter()
+            .map(|contour| {
+                let points: Vec<Point> = contour
+                    .iter()
+                    .flat_map(|primitive| primitive.sample(16))
+                    .collect();
+                let doubled: f32 = points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+                    .sum();
+                doubled / 2.0
+            })
+            .sum()
+    }
+
+    /// Splits the primitives into contours, starting a new one wherever a primitive does not
+    /// continue from the end of the previous one
+    pub fn contours(&self) -> Vec<&[Primitive]> {
+        let mut contours = vec![];
+        let mut start = 0;
+        for i in 1..self.primitives.len() {
+            if self.primitives[i].start() != self.primitives[i - 1].end() {
+                contours.push(&self.primitives[start..i]);
+                start = i;
+            }
+        }
+        if start < self.primitives.len() {
+            contours.push(&self.primitives[start..]);
+        }
+        contours
+    }
+
+    /// Returns the minimum and maximum corners of the box enclosing every point, including
+    /// control points, or `None` if the shape has no primitives. NaN coordinates are ordered with
+    /// `f32::total_cmp` instead of panicking, see [`check_finite`] to reject them
+    pub fn get_bb(&self) -> Option<(Point, Point)> {
+        let points: Vec<&Point> = self.primitives.iter().flat_map(Primitive::points).collect();
+        let extent = |coordinate: fn(&Point) -> f32| {
+            let values = points.iter().map(|point| coordinate(point));
+            Some((
+                values.clone().min_by(f32::total_cmp)?,
+                values.max_by(f32::total_cmp)?,
+            ))
+        };
+        let (min_x, max_x) = extent(|point| point.0)?;
+        let (min_y, max_y) = extent(|point| point.1)?;
+        Some((Point(min_x, min_y), Point(max_x, max_y)))
+    }
+
+    /// Whether every coordinate of the shape is a finite number
+    pub fn is_finite(&self) -> bool {
+        self.primitives
+            .iter()
+            .flat_map(Primitive::points)
+            .chain(self.origin.iter())
+            .all(Point::is_finite)
+    }
+
+    /// Applies `f` to every point of every primitive, and to the origin and the pen position of
+    /// the layout box if there are any
+    pub fn map_points(self, f: impl Fn(Point) -> Point) -> Self {
+        let origin = self.origin.clone().map(&f);
+        let layout_box = self.layout_box.clone().map(|layout_box| LayoutBox {
+            pen: f(layout_box.pen),
+            ..layout_box
+        });
+        Self {
+            origin,
+            layout_box,
+            ..self.map_primitives(|primitives| {
+                primitives
+                    .into_iter()
+                    .map(|primitive| primitive.map_points(&f))
+                    .collect()
+            })
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::step::step_real;
+use crate::{Point, Primitive, Shape};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Renders the shapes as an IGES 5.3 file holding the outline of every glyph as a wireframe,
+/// the same geometry [`crate::step::to_step`] writes for programs that prefer IGES.
+///
+/// Lines become line entities (110) and curves rational B-spline entities (126) with the
+/// control points of the primitive as poles and unit weights, so the outline is reproduced
+/// exactly. Every contour is a composite curve (102), and the contours of a glyph are collected
+/// in an unordered group (402, form 7). Only the 2D profile is written, in the XY plane, with
+/// coordinates taken as millimetres. The file carries no creation date, so the same text always
+/// gives the same file.
+pub fn to_iges(shapes: &[Shape]) -> String {
+    let mut glyphs: BTreeMap<usize, Vec<&Shape>> = BTreeMap::new();
+    for shape in shapes.iter().filter(|shape| !shape.primitives.is_empty()) {
+        glyphs.entry(shape.index).or_default().push(shape);
+    }
+
+    let mut iges = Iges::default();
+    for shapes in glyphs.values() {
+        let mut curves = vec![];
+        for contour in shapes.iter().flat_map(|shape| shape.contours()) {
+            let segments: Vec<String> = contour
+                .iter()
+                // Zero length edges are rejected by CAD kernels, as in the STEP output
+                .filter(|primitive| !matches!(primitive, Primitive::Close(p0, p1) if p0 == p1))
+                .map(|primitive| iges.curve(primitive))
+                .collect();
+            if !segments.is_empty() {
+                curves.push(iges.entity(
+                    102,
+                    0,
+                    INDEPENDENT,
+                    format!("{},{}", segments.len(), segments.join(",")),
+                ));
+            }
+        }
+        if !curves.is_empty() {
+            iges.entity(
+                402,
+                7,
+                INDEPENDENT,
+                format!("{},{}", curves.len(), curves.join(",")),
+            );
+        }
+    }
+
+    let start = ["Text outlines as a 2D wireframe, written by fonts".to_string()];
+    let global = records(&global_section(), 72);
+    let mut out = String::new();
+    for (section, lines) in [('S', &start[..]), ('G', &global[..])] {
+        for (number, line) in lines.iter().enumerate() {
+            writeln!(out, "{line:<72}{section}{:>7}", number + 1)
+                .expect("Writing to a String cannot fail");
+        }
+    }
+    out.push_str(&iges.directory);
+    out.push_str(&iges.parameters);
+    writeln!(
+        out,
+        "{:<72}T{:>7}",
+        format!(
+            "S{:>7}G{:>7}D{:>7}P{:>7}",
+            start.len(),
+            global.len(),
+            iges.directory_lines,
+            iges.parameter_lines
+        ),
+        1
+    )
+    .expect("Writing to a String cannot fail");
+    out
+}
+
+/// Status of an entity that stands on its own
+const INDEPENDENT: &str = "00000000";
+/// Status of an entity that only exists as part of the curve referencing it
+const SUBORDINATE: &str = "00010000";
+
+/// The directory entry and parameter data sections being written, numbering their lines in order
+#[derive(Default)]
+struct Iges {
+    directory: String,
+    directory_lines: usize,
+    parameters: String,
+    parameter_lines: usize,
+}
+
+impl Iges {
+    /// Appends the entity, `parameters` being its parameter data after the entity type, and
+    /// returns the pointer to its directory entry
+    fn entity(&mut self, kind: u32, form: u32, status: &str, parameters: String) -> String {
+        let pointer = self.directory_lines + 1;
+        let lines = records(&format!("{kind},{parameters};"), 64);
+        for (line, parameter_line) in lines.iter().zip(self.parameter_lines + 1..) {
+            writeln!(
+                self.parameters,
+                "{line:<64} {pointer:>7}P{parameter_line:>7}"
+            )
+            .expect("Writing to a String cannot fail");
+        }
+        let first_line = self.parameter_lines + 1;
+        self.parameter_lines += lines.len();
+
+        writeln!(
+            self.directory,
+            "{kind:>8}{first_line:>8}{:>8}{:>8}{:>8}{:>8}{:>8}{:>8}{status:>8}D{pointer:>7}",
+            0, 0, 0, 0, 0, 0
+        )
+        .expect("Writing to a String cannot fail");
+        writeln!(
+            self.directory,
+            "{kind:>8}{:>8}{:>8}{:>8}{form:>8}{:>8}{:>8}{:>8}{:>8}D{:>7}",
+            0,
+            0,
+            lines.len(),
+            "",
+            "",
+            "",
+            0,
+            pointer + 1
+        )
+        .expect("Writing to a String cannot fail");
+        self.directory_lines += 2;
+        pointer.to_string()
+    }
+
+    /// The primitive as a line entity, or as a single Bezier segment: a rational B-spline with
+    /// unit weights and no interior knots
+    fn curve(&mut self, primitive: &Primitive) -> String {
+        let poles = primitive.points();
+        let coordinates = poles
+            .iter()
+            .map(|point| iges_point(point))
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Primitive::Line(..) | Primitive::Close(..) = primitive {
+            return self.entity(110, 0, SUBORDINATE, coordinates);
+        }
+        let degree = poles.len() - 1;
+        let knots = [vec!["0."; degree + 1], vec!["1."; degree + 1]].concat();
+        let weights = vec!["1."; poles.len()];
+        self.entity(
+            126,
+            0,
+            SUBORDINATE,
+            format!(
+                "{degree},{degree},1,0,1,0,{},{},{},0.,1.,0.,0.,1.",
+                knots.join(","),
+                weights.join(","),
+                coordinates
+            ),
+        )
+    }
+}
+
+/// The global section: comma and semicolon as delimiters, millimetres as the unit and 5.3 as
+/// the version of the format
+fn global_section() -> String {
+    let fields = [
+        hollerith(","),
+        hollerith(";"),
+        hollerith("Text"),
+        hollerith("text.igs"),
+        hollerith("fonts"),
+        hollerith(env!("CARGO_PKG_VERSION")),
+        "32".to_string(),
+        "38".to_string(),
+        "6".to_string(),
+        "308".to_string(),
+        "15".to_string(),
+        hollerith("Text"),
+        "1.".to_string(),
+        "2".to_string(),
+        hollerith("MM"),
+        "1".to_string(),
+        "1.".to_string(),
+        String::new(),
+        "1.E-07".to_string(),
+        "0.".to_string(),
+        String::new(),
+        String::new(),
+        "11".to_string(),
+        "0".to_string(),
+    ];
+    fields.join(",") + ";"
+}
+
+/// The point in the XY plane, its reals written the same way as in STEP
+fn iges_point(point: &Point) -> String {
+    format!("{},{},0.", step_real(point.0), step_real(point.1))
+}
+
+/// Quotes the text as an IGES string, prefixed with its length
+fn hollerith(text: &str) -> String {
+    format!("{}H{text}", text.len())
+}
+
+/// Splits the parameters of a section into lines of at most `width` columns, breaking after a
+/// delimiter so no parameter is cut in two
+fn records(parameters: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for parameter in parameters.split_inclusive([',', ';']) {
+        let line = lines.last_mut().expect("There is always a line");
+        if line.len() + parameter.len() > width {
+            lines.push(parameter.to_string());
+        } else {
+            line.push_str(parameter);
+        }
+    }
+    lines
+}
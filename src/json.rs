@@ -0,0 +1,221 @@
+use crate::nest::Region;
+use crate::{Orientation, Point, Primitive, Shape, TextMetrics, Units};
+use serde::Serialize;
+use std::io::Write;
+
+/// Version of the structure written by [`to_json`], [`to_nested_json`] and [`to_line_json`],
+/// bumped whenever it changes. Version 1 is the bare array written by [`to_legacy_json`].
+/// Version 3 added the lines of [`to_line_json`], [`Primitive::Close`] and the `line`,
+/// `source_range`, `length`, `layout_box` and `bbox` fields of a shape
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct Output<'a> {
+    version: u32,
+    bbox: BoundingBox<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    units: Option<Units>,
+    metrics: &'a TextMetrics,
+    #[serde(flatten)]
+    geometry: Geometry<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Geometry<'a> {
+    Shapes(&'a [Shape]),
+    Regions(&'a [Region]),
+    Lines(&'a [Line<'a>]),
+    #[serde(rename = "shapes")]
+    ShapesV2(Vec<ShapeV2<'a>>),
+    #[serde(rename = "regions")]
+    RegionsV2(Vec<RegionV2<'a>>),
+}
+
+/// A shape with only the fields of version 2
+#[derive(Serialize)]
+struct ShapeV2<'a> {
+    primitives: &'a [Primitive],
+    #[serde(rename = "char")]
+    character: &'a str,
+    index: usize,
+    contour: usize,
+    orientation: &'a Option<Orientation>,
+    closed: bool,
+    area: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stroke: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: &'a Option<Point>,
+}
+
+impl<'a> From<&'a Shape> for ShapeV2<'a> {
+    fn from(shape: &'a Shape) -> Self {
+        Self {
+            primitives: &shape.primitives,
+            character: &shape.character,
+            index: shape.index,
+            contour: shape.contour,
+            orientation: &shape.orientation,
+            closed: shape.closed,
+            area: shape.area,
+            stroke: shape.stroke,
+            origin: &shape.origin,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RegionV2<'a> {
+    outer: ShapeV2<'a>,
+    holes: Vec<ShapeV2<'a>>,
+}
+
+/// The shapes of one laid out line, see [`group_by_line`]
+#[derive(Debug, Serialize)]
+pub struct Line<'a> {
+    /// Index of the line, counting wrapped lines separately
+    pub line: usize,
+    /// Start of the baseline of the line
+    pub baseline: &'a Point,
+    pub shapes: Vec<&'a Shape>,
+}
+
+/// Groups the shapes by the line they are on, `baselines` holding the start of the baseline of
+/// every line as returned in [`crate::Rendering`]. Lines without shapes are left out
+pub fn group_by_line<'a>(shapes: &'a [Shape], baselines: &'a [Point]) -> Vec<Line<'a>> {
+    baselines
+        .iter()
+        .enumerate()
+        .map(|(line, baseline)| Line {
+            line,
+            baseline,
+            shapes: shapes.iter().filter(|shape| shape.line == line).collect(),
+        })
+        .filter(|line| !line.shapes.is_empty())
+        .collect()
+}
+
+#[derive(Serialize)]
+struct BoundingBox<'a> {
+    min: &'a Point,
+    max: &'a Point,
+}
+
+/// Serializes the shapes together with their combined bounding box, the unit of the coordinates
+/// and the text metrics, indented when `pretty` is set. `version` is [`SCHEMA_VERSION`], or 2 to
+/// leave out the shape fields added in version 3 for older importers. [`Primitive::Close`] has
+/// no version 2 form, so shapes written as version 2 should not contain it
+pub fn to_json(
+    shapes: &[Shape],
+    min_point: &Point,
+    max_point: &Point,
+    units: Option<Units>,
+    metrics: &TextMetrics,
+    version: u32,
+    pretty: bool,
+) -> Result<String, serde_json::Error> {
+    let geometry = match version {
+        2 => Geometry::ShapesV2(shapes.iter().map(ShapeV2::from).collect()),
+        _ => Geometry::Shapes(shapes),
+    };
+    to_document(
+        geometry, version, min_point, max_point, units, metrics, pretty,
+    )
+}
+
+/// Like [`to_json`], with the shapes grouped into outer contours and their holes, see
+/// [`crate::nest::nest`]
+pub fn to_nested_json(
+    regions: &[Region],
+    min_point: &Point,
+    max_point: &Point,
+    units: Option<Units>,
+    metrics: &TextMetrics,
+    version: u32,
+    pretty: bool,
+) -> Result<String, serde_json::Error> {
+    let geometry = match version {
+        2 => Geometry::RegionsV2(
+            regions
+                .iter()
+                .map(|region| RegionV2 {
+                    outer: ShapeV2::from(&region.outer),
+                    holes: region.holes.iter().map(ShapeV2::from).collect(),
+                })
+                .collect(),
+        ),
+        _ => Geometry::Regions(regions),
+    };
+    to_document(
+        geometry, version, min_point, max_point, units, metrics, pretty,
+    )
+}
+
+/// Like [`to_json`], with the shapes grouped by line, see [`group_by_line`]. Lines were added in
+/// version 3, so this always writes [`SCHEMA_VERSION`]
+pub fn to_line_json(
+    lines: &[Line],
+    min_point: &Point,
+    max_point: &Point,
+    units: Option<Units>,
+    metrics: &TextMetrics,
+    pretty: bool,
+) -> Result<String, serde_json::Error> {
+    to_document(
+        Geometry::Lines(lines),
+        SCHEMA_VERSION,
+        min_point,
+        max_point,
+        units,
+        metrics,
+        pretty,
+    )
+}
+
+fn to_document(
+    geometry: Geometry,
+    version: u32,
+    min_point: &Point,
+    max_point: &Point,
+    units: Option<Units>,
+    metrics: &TextMetrics,
+    pretty: bool,
+) -> Result<String, serde_json::Error> {
+    serialize(
+        &Output {
+            version,
+            bbox: BoundingBox {
+                min: min_point,
+                max: max_point,
+            },
+            units,
+            metrics,
+            geometry,
+        },
+        pretty,
+    )
+}
+
+/// Serializes the shapes as a bare array, the format used before the bounding box was included
+pub fn to_legacy_json(shapes: &[Shape], pretty: bool) -> Result<String, serde_json::Error> {
+    serialize(shapes, pretty)
+}
+
+/// Writes one shape per line as newline delimited JSON, serializing each shape straight into
+/// `writer` instead of into one string. The shapes themselves are already all in memory
+pub fn write_ndjson(shapes: &[Shape], mut writer: impl Write) -> std::io::Result<()> {
+    for shape in shapes {
+        serde_json::to_writer(&mut writer, shape)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn serialize<T: Serialize + ?Sized>(value: &T, pretty: bool) -> Result<String, serde_json::Error> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
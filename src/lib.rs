@@ -0,0 +1,1964 @@
+//! Turns text into vector outlines ready to be imported into CAD programs.
+//!
+//! Text is shaped and laid out with cosmic-text, then every glyph outline is converted into
+//! [`Shape`]s made of lines and Bezier curves. [`text_to_shapes`] runs the whole pipeline, and the
+//! [`json`], [`svg`] and [`dxf`] modules serialize its result.
+
+use cosmic_text::{
+    Align, Angle, Attrs, BidiParagraphs, Buffer, CacheKey, Command, Family, FontSystem,
+    LayoutGlyph, Metrics, Shaping, Stretch, Style, SwashCache, Transform, Weight,
+};
+use fontdb::{FaceInfo, Source, ID};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+use swash::scale::ScaleContext;
+use swash::zeno::PathData;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+pub mod boolean;
+pub mod clean;
+pub mod dxf;
+mod error;
+pub mod fcmacro;
+pub mod flatten;
+pub mod gcode;
+mod geometry;
+#[cfg(feature = "step")]
+pub mod iges;
+pub mod json;
+pub mod nest;
+pub mod simplify;
+#[cfg(feature = "step")]
+pub mod step;
+pub mod svg;
+
+pub use error::{Error, Warning};
+pub use geometry::{
+    bounding_box, check_finite, Bounds, LayoutBox, Orientation, Point, Primitive, Shape,
+    CLOSED_EPSILON,
+};
+
+/// How the combined geometry is scaled into physical units
+#[derive(Clone, Copy, Debug)]
+pub enum TargetSize {
+    /// Scale uniformly so the longest side has this length
+    Longest(f32),
+    /// Scale each axis so the geometry has exactly this width and height
+    Exact(f32, f32),
+    /// Scale uniformly so the widest line has this width, e.g. to fit a label of fixed width
+    Width(f32),
+}
+
+/// Fill rule the winding of the output contours is prepared for
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FillRule {
+    /// Contours keep the winding they have in the font, which only the even-odd rule ignores
+    #[default]
+    EvenOdd,
+    /// Outer contours run counter-clockwise and holes clockwise
+    NonZero,
+}
+
+/// Vertical position of the text relative to the baseline of regular text
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Script {
+    #[default]
+    Normal,
+    Superscript,
+    Subscript,
+}
+
+impl Script {
+    /// Size of synthesized superscript and subscript glyphs, relative to the font size
+    const SIZE: f32 = 0.65;
+
+    /// Shift of the baseline, in ems, upwards for superscripts
+    fn baseline_shift(self) -> f32 {
+        match self {
+            Script::Normal => 0.0,
+            Script::Superscript => 0.35,
+            Script::Subscript => -0.15,
+        }
+    }
+}
+
+/// Unicode normalization form the text is converted to before shaping
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalizationForm {
+    /// Canonical composition, so "e" followed by a combining acute becomes a single "é"
+    #[default]
+    Nfc,
+    /// Canonical decomposition, splitting precomposed characters into a base and its marks
+    Nfd,
+}
+
+/// Physical unit of the output coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Units {
+    #[serde(rename = "mm")]
+    Millimetres,
+    #[serde(rename = "in")]
+    Inches,
+    #[serde(rename = "pt")]
+    Points,
+}
+
+impl Units {
+    /// Length of a typographic point, 1/72 of an inch, in this unit
+    pub fn per_point(self) -> f32 {
+        match self {
+            Units::Millimetres => 25.4 / 72.0,
+            Units::Inches => 1.0 / 72.0,
+            Units::Points => 1.0,
+        }
+    }
+}
+
+/// Base direction of each paragraph, which decides the visual order of mixed direction text
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BaseDirection {
+    /// Use the direction of the first strongly directional character, as the Unicode bidi
+    /// algorithm does
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl BaseDirection {
+    /// The invisible mark prepended to each line to force this direction. cosmic-text always
+    /// detects the paragraph direction itself, so this is the only way to override it
+    fn mark(self) -> Option<char> {
+        match self {
+            BaseDirection::Auto => None,
+            BaseDirection::LeftToRight => Some('\u{200E}'),
+            BaseDirection::RightToLeft => Some('\u{200F}'),
+        }
+    }
+}
+
+/// Configuration of the text to geometry pipeline
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Font size, in pixels, used when shaping the text
+    pub font_size: f32,
+    /// Size, in pixels, the glyphs are positioned and their outlines extracted at before being
+    /// scaled back to the font size. Glyphs are placed on whole pixels, so a size above the font
+    /// size places them more precisely. Defaults to the font size
+    pub render_size: Option<f32>,
+    /// Device pixels per pixel of the font size, the scale the laid out glyphs are positioned
+    /// and extracted at. Unlike the render size the geometry stays at this scale, so a factor of 2
+    /// doubles every coordinate before normalization. Glyphs are snapped to whole device pixels
+    pub scale_factor: f32,
+    /// Line height, in pixels. Defaults to 1.4 times the font size
+    pub line_height: Option<f32>,
+    /// Extra spacing, in pixels, added between consecutive lines
+    pub line_gap: f32,
+    /// Wrap lines longer than this width, in pixels
+    pub wrap_width: Option<f32>,
+    /// Alignment of each line within the widest line, or within `wrap_width` when set. Letter
+    /// spacing is added after alignment and widens lines without moving them
+    pub align: Option<Align>,
+    /// Normalization applied to the text before shaping, so composed and decomposed input
+    /// produce the same glyphs. The text is shaped as given when `None`
+    pub normalize_unicode: Option<NormalizationForm>,
+    /// Only emit the glyphs whose cluster starts within this range of character indices, counted
+    /// in the text once Unicode normalization is applied. The whole text is still shaped, so
+    /// kerning and ligatures take the characters around the range into account, and laid out and
+    /// scaled, so the glyphs are placed exactly where they are in the whole text
+    pub range: Option<Range<usize>>,
+    /// Stack the glyphs of every line top to bottom, using the vertical advances of the font, with
+    /// the lines as columns from right to left, e.g. for CJK signage. cosmic-text only lays text
+    /// out horizontally, so glyphs keep their horizontal forms and are centred on the column
+    pub vertical: bool,
+    /// Distance between tab stops, in widths of a space. A tab advances to the next stop
+    pub tab_width: u16,
+    /// Name of the font family to use
+    pub font_family: Option<String>,
+    /// Families tried in order for characters `font_family` has no glyph for, before the
+    /// fallback of cosmic-text
+    pub fallback_families: Vec<String>,
+    /// Font weight to request
+    pub weight: Option<Weight>,
+    /// Font style to request
+    pub style: Option<Style>,
+    /// Font stretch to request
+    pub stretch: Option<Stretch>,
+    /// Coordinates on the axes of a variable font, such as `wght` or `wdth`. Only the outlines
+    /// follow them, glyphs keep the advances of the default instance
+    pub variations: Vec<([u8; 4], f32)>,
+    /// Render the text as a superscript or subscript. cosmic-text cannot select the `sups` and
+    /// `subs` features of a font, so the glyphs are synthesized by shrinking the regular glyphs
+    /// and shifting their baseline, while the line keeps the height of regular text
+    pub script: Script,
+    /// Shear, in degrees, applied to glyphs whose face does not have the requested style
+    pub synthetic_italic: Option<f32>,
+    /// Shear, in degrees, applied to every glyph around its baseline, slanting the text to the
+    /// right for positive angles
+    pub shear: Option<f32>,
+    /// Shaping strategy, Basic is faster but skips the complex cluster handling some scripts need
+    pub shaping: Shaping,
+    /// Base direction of each paragraph
+    pub base_direction: BaseDirection,
+    /// Extra space, in pixels, inserted between consecutive characters of a line
+    pub letter_spacing: f32,
+    /// Apply the kerning of the font, otherwise every glyph sits at its nominal advance
+    pub kerning: bool,
+    /// Use the layers of color glyphs, such as emoji, merged into a single monochrome outline
+    /// instead of the plain outline the font provides for them. The layers are overlaid, not
+    /// united
+    pub flatten_color: bool,
+    /// Fail with [`Error::MissingGlyphs`] when a character has no glyph in any available font,
+    /// instead of warning and rendering the font's placeholder box, and with
+    /// [`Error::NoOutline`] when a glyph has no outline, instead of warning and leaving it out
+    pub strict: bool,
+    /// Attach the layout box of its glyph to every shape, e.g. to reconstruct cursor positions
+    pub layout_boxes: bool,
+    /// Attach the box enclosing its glyph, in output coordinates, to every shape
+    pub glyph_boxes: bool,
+    /// Compute the path length of every shape, e.g. to estimate cutting times
+    pub lengths: bool,
+    /// Tag every contour as an open stroke instead of a filled region
+    pub stroke: bool,
+    /// Emit a shape without primitives for glyphs without an outline, such as spaces, recording
+    /// where they were placed
+    pub emit_empty: bool,
+    /// Rotation, in degrees counter-clockwise, of the whole text around the center of its
+    /// bounding box
+    pub rotate: Option<f32>,
+    /// Mirror the text horizontally, negating every X coordinate
+    pub mirror_x: bool,
+    /// Mirror the text vertically, negating every Y coordinate
+    pub mirror_y: bool,
+    /// Negate every Y coordinate, producing Y-down coordinates
+    pub flip_y: bool,
+    /// Normalize the geometry into a 0..1 box, otherwise coordinates are in pixels
+    pub normalize: bool,
+    /// Once normalized, scale every glyph into a 0..1 box of its own, for symbol libraries. This
+    /// loses the relative sizes of the letters, the metrics and baselines follow the combined box
+    pub per_glyph_normalize: bool,
+    /// Margin left around the normalized geometry, as a fraction of the 0..1 box below 0.5. The
+    /// target size then applies to the box including the margin
+    pub bbox_pad: Option<f32>,
+    /// Scale the geometry into physical units after normalization
+    pub target_size: Option<TargetSize>,
+    /// Unit of the output coordinates. When `normalize` is off the font size is taken in points
+    /// and the laid out geometry converted into this unit
+    pub units: Option<Units>,
+    /// Uniform factor applied to the geometry after normalization and the target size, to the
+    /// raw pixel coordinates when `normalize` is off
+    pub scale: Option<f32>,
+    /// Move the origin onto this point after scaling, otherwise the origin is kept where the
+    /// normalization or the layout put it
+    pub origin: Option<Origin>,
+    /// Translation applied last, once the geometry is scaled and the origin placed
+    pub offset: Option<(f32, f32)>,
+    /// Convert every quadratic curve into the equivalent cubic Bezier
+    pub cubic_only: bool,
+    /// Record the close of every contour of the outline as a [`Primitive::Close`] back to its
+    /// first point, even when the contour already ends there, instead of a line that is left out
+    /// when it has zero length
+    pub keep_close: bool,
+    /// Replace curves with lines deviating at most this far from the curve, in output units
+    pub flatten: Option<f32>,
+    /// Merge overlapping contours into the outline of the region they cover, see
+    /// [`boolean::union_shapes`]. Curves are flattened first, with the `flatten` tolerance if set
+    pub union: bool,
+    /// Add a rectangle around the text, the normalized box including `bbox_pad` or the bounding
+    /// box of the glyphs, and wind the glyphs as holes cut out of it, e.g. for stencils. The
+    /// rectangle is the last shape, with an empty text and the index after the last glyph
+    pub invert: bool,
+    /// Most lines a single curve is flattened into, see [`flatten::flatten_shape`]
+    pub max_segments: usize,
+    /// Drop vertices of line chains deviating at most this far from the simplified chain, in
+    /// output units
+    pub simplify: Option<f32>,
+    /// Drop closed contours enclosing less than this area, in output units squared, such as
+    /// specks and slivers left by the font
+    pub min_contour_area: Option<f32>,
+    /// Replace curves whose control points lie less than this far from their chord with lines, in
+    /// output units. Defaults to one unit of the last decimal place kept by `precision`, below
+    /// which rounding would hide the curve anyway, and 0 keeps every curve
+    pub straighten: Option<f32>,
+    /// Fill rule the winding of the contours is corrected for
+    pub fill_rule: FillRule,
+    /// Trace every contour the other way round once the winding is corrected for the fill rule,
+    /// for consumers expecting the opposite convention
+    pub reverse_contours: bool,
+    /// Number of decimal places kept in the output coordinates
+    pub precision: u32,
+    /// Sort the contours of each glyph by the minimum corner of their bounding box, instead of
+    /// keeping the order they have in the font
+    pub stable_order: bool,
+    /// Convert the outline of every unique glyph once and only place it for each occurrence.
+    /// Turning it off converts the outline again every time, which is only useful to measure
+    /// what the reuse saves
+    pub reuse_outlines: bool,
+}
+
+impl Default for RenderOptions {
+    /// The defaults of the command line tool: 14 pixel text normalized into a 0..1 box, with
+    /// NFC normalization, kerning and three decimal places
+    fn default() -> Self {
+        Self {
+            font_size: 14.0,
+            render_size: None,
+            scale_factor: 1.0,
+            line_height: None,
+            line_gap: 0.0,
+            wrap_width: None,
+            align: None,
+            normalize_unicode: Some(NormalizationForm::Nfc),
+            range: None,
+            vertical: false,
+            tab_width: 8,
+            font_family: None,
+            fallback_families: vec![],
+            weight: None,
+            style: None,
+            stretch: None,
+            variations: vec![],
+            script: Script::Normal,
+            synthetic_italic: None,
+            shear: None,
+            shaping: Shaping::Advanced,
+            base_direction: BaseDirection::Auto,
+            letter_spacing: 0.0,
+            kerning: true,
+            flatten_color: false,
+            strict: false,
+            layout_boxes: false,
+            glyph_boxes: false,
+            lengths: false,
+            stroke: false,
+            emit_empty: false,
+            rotate: None,
+            mirror_x: false,
+            mirror_y: false,
+            flip_y: false,
+            normalize: true,
+            per_glyph_normalize: false,
+            bbox_pad: None,
+            target_size: None,
+            units: None,
+            scale: None,
+            origin: None,
+            offset: None,
+            cubic_only: false,
+            keep_close: false,
+            flatten: None,
+            union: false,
+            invert: false,
+            max_segments: flatten::DEFAULT_MAX_SEGMENTS,
+            simplify: None,
+            min_contour_area: None,
+            straighten: None,
+            fill_rule: FillRule::EvenOdd,
+            reverse_contours: false,
+            precision: 3,
+            stable_order: false,
+            reuse_outlines: true,
+        }
+    }
+}
+
+/// Builder methods, each setting the field of the same name. Options are switched on by passing
+/// their value, e.g. `RenderOptions::default().font_size(24.0).flatten(0.01)`
+impl RenderOptions {
+    /// Sets [`RenderOptions::font_size`].
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Sets [`RenderOptions::render_size`].
+    pub fn render_size(mut self, render_size: f32) -> Self {
+        self.render_size = Some(render_size);
+        self
+    }
+
+    /// Sets [`RenderOptions::scale_factor`].
+    pub fn scale_factor(mut self, scale_factor: f32) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    /// Sets [`RenderOptions::line_height`].
+    pub fn line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
+        self
+    }
+
+    /// Sets [`RenderOptions::line_gap`].
+    pub fn line_gap(mut self, line_gap: f32) -> Self {
+        self.line_gap = line_gap;
+        self
+    }
+
+    /// Sets [`RenderOptions::wrap_width`].
+    pub fn wrap_width(mut self, wrap_width: f32) -> Self {
+        self.wrap_width = Some(wrap_width);
+        self
+    }
+
+    /// Sets [`RenderOptions::align`].
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = Some(align);
+        self
+    }
+
+    /// Sets [`RenderOptions::normalize_unicode`].
+    pub fn normalize_unicode(mut self, normalize_unicode: Option<NormalizationForm>) -> Self {
+        self.normalize_unicode = normalize_unicode;
+        self
+    }
+
+    /// Sets [`RenderOptions::range`].
+    pub fn range(mut self, range: Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Sets [`RenderOptions::vertical`].
+    pub fn vertical(mut self, vertical: bool) -> Self {
+        self.vertical = vertical;
+        self
+    }
+
+    /// Sets [`RenderOptions::tab_width`].
+    pub fn tab_width(mut self, tab_width: u16) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// Sets [`RenderOptions::font_family`].
+    pub fn font_family(mut self, font_family: impl Into<String>) -> Self {
+        self.font_family = Some(font_family.into());
+        self
+    }
+
+    /// Sets [`RenderOptions::fallback_families`].
+    pub fn fallback_families(mut self, fallback_families: Vec<String>) -> Self {
+        self.fallback_families = fallback_families;
+        self
+    }
+
+    /// Sets [`RenderOptions::weight`].
+    pub fn weight(mut self, weight: Weight) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Sets [`RenderOptions::style`].
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// Sets [`RenderOptions::stretch`].
+    pub fn stretch(mut self, stretch: Stretch) -> Self {
+        self.stretch = Some(stretch);
+        self
+    }
+
+    /// Sets [`RenderOptions::variations`].
+    pub fn variations(mut self, variations: Vec<([u8; 4], f32)>) -> Self {
+        self.variations = variations;
+        self
+    }
+
+    /// Sets [`RenderOptions::script`].
+    pub fn script(mut self, script: Script) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Sets [`RenderOptions::synthetic_italic`].
+    pub fn synthetic_italic(mut self, synthetic_italic: f32) -> Self {
+        self.synthetic_italic = Some(synthetic_italic);
+        self
+    }
+
+    /// Sets [`RenderOptions::shear`].
+    pub fn shear(mut self, shear: f32) -> Self {
+        self.shear = Some(shear);
+        self
+    }
+
+    /// Sets [`RenderOptions::shaping`].
+    pub fn shaping(mut self, shaping: Shaping) -> Self {
+        self.shaping = shaping;
+        self
+    }
+
+    /// Sets [`RenderOptions::base_direction`].
+    pub fn base_direction(mut self, base_direction: BaseDirection) -> Self {
+        self.base_direction = base_direction;
+        self
+    }
+
+    /// Sets [`RenderOptions::letter_spacing`].
+    pub fn letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = letter_spacing;
+        self
+    }
+
+    /// Sets [`RenderOptions::kerning`].
+    pub fn kerning(mut self, kerning: bool) -> Self {
+        self.kerning = kerning;
+        self
+    }
+
+    /// Sets [`RenderOptions::flatten_color`].
+    pub fn flatten_color(mut self, flatten_color: bool) -> Self {
+        self.flatten_color = flatten_color;
+        self
+    }
+
+    /// Sets [`RenderOptions::strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets [`RenderOptions::layout_boxes`].
+    pub fn layout_boxes(mut self, layout_boxes: bool) -> Self {
+        self.layout_boxes = layout_boxes;
+        self
+    }
+
+    /// Sets [`RenderOptions::glyph_boxes`].
+    pub fn glyph_boxes(mut self, glyph_boxes: bool) -> Self {
+        self.glyph_boxes = glyph_boxes;
+        self
+    }
+
+    /// Sets [`RenderOptions::lengths`].
+    pub fn lengths(mut self, lengths: bool) -> Self {
+        self.lengths = lengths;
+        self
+    }
+
+    /// Sets [`RenderOptions::stroke`].
+    pub fn stroke(mut self, stroke: bool) -> Self {
+        self.stroke = stroke;
+        self
+    }
+
+    /// Sets [`RenderOptions::emit_empty`].
+    pub fn emit_empty(mut self, emit_empty: bool) -> Self {
+        self.emit_empty = emit_empty;
+        self
+    }
+
+    /// Sets [`RenderOptions::rotate`].
+    pub fn rotate(mut self, rotate: f32) -> Self {
+        self.rotate = Some(rotate);
+        self
+    }
+
+    /// Sets [`RenderOptions::mirror_x`].
+    pub fn mirror_x(mut self, mirror_x: bool) -> Self {
+        self.mirror_x = mirror_x;
+        self
+    }
+
+    /// Sets [`RenderOptions::mirror_y`].
+    pub fn mirror_y(mut self, mirror_y: bool) -> Self {
+        self.mirror_y = mirror_y;
+        self
+    }
+
+    /// Sets [`RenderOptions::flip_y`].
+    pub fn flip_y(mut self, flip_y: bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Sets [`RenderOptions::normalize`].
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Sets [`RenderOptions::per_glyph_normalize`].
+    pub fn per_glyph_normalize(mut self, per_glyph_normalize: bool) -> Self {
+        self.per_glyph_normalize = per_glyph_normalize;
+        self
+    }
+
+    /// Sets [`RenderOptions::bbox_pad`].
+    pub fn bbox_pad(mut self, bbox_pad: f32) -> Self {
+        self.bbox_pad = Some(bbox_pad);
+        self
+    }
+
+    /// Sets [`RenderOptions::target_size`].
+    pub fn target_size(mut self, target_size: TargetSize) -> Self {
+        self.target_size = Some(target_size);
+        self
+    }
+
+    /// Sets [`RenderOptions::units`].
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    /// Sets [`RenderOptions::scale`].
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Sets [`RenderOptions::origin`].
+    pub fn origin(mut self, origin: Origin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Sets [`RenderOptions::offset`].
+    pub fn offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = Some((x, y));
+        self
+    }
+
+    /// Sets [`RenderOptions::cubic_only`].
+    pub fn cubic_only(mut self, cubic_only: bool) -> Self {
+        self.cubic_only = cubic_only;
+        self
+    }
+
+    /// Sets [`RenderOptions::keep_close`].
+    pub fn keep_close(mut self, keep_close: bool) -> Self {
+        self.keep_close = keep_close;
+        self
+    }
+
+    /// Sets [`RenderOptions::flatten`].
+    pub fn flatten(mut self, flatten: f32) -> Self {
+        self.flatten = Some(flatten);
+        self
+    }
+
+    /// Sets [`RenderOptions::union`].
+    pub fn union(mut self, union: bool) -> Self {
+        self.union = union;
+        self
+    }
+
+    /// Sets [`RenderOptions::invert`].
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    /// Sets [`RenderOptions::max_segments`].
+    pub fn max_segments(mut self, max_segments: usize) -> Self {
+        self.max_segments = max_segments;
+        self
+    }
+
+    /// Sets [`RenderOptions::simplify`].
+    pub fn simplify(mut self, simplify: f32) -> Self {
+        self.simplify = Some(simplify);
+        self
+    }
+
+    /// Sets [`RenderOptions::min_contour_area`].
+    pub fn min_contour_area(mut self, min_contour_area: f32) -> Self {
+        self.min_contour_area = Some(min_contour_area);
+        self
+    }
+
+    /// Sets [`RenderOptions::straighten`].
+    pub fn straighten(mut self, straighten: f32) -> Self {
+        self.straighten = Some(straighten);
+        self
+    }
+
+    /// Sets [`RenderOptions::fill_rule`].
+    pub fn fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Sets [`RenderOptions::reverse_contours`].
+    pub fn reverse_contours(mut self, reverse_contours: bool) -> Self {
+        self.reverse_contours = reverse_contours;
+        self
+    }
+
+    /// Sets [`RenderOptions::precision`].
+    pub fn precision(mut self, precision: u32) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Sets [`RenderOptions::stable_order`].
+    pub fn stable_order(mut self, stable_order: bool) -> Self {
+        self.stable_order = stable_order;
+        self
+    }
+
+    /// Sets [`RenderOptions::reuse_outlines`].
+    pub fn reuse_outlines(mut self, reuse_outlines: bool) -> Self {
+        self.reuse_outlines = reuse_outlines;
+        self
+    }
+}
+
+/// Where the origin of the output coordinates is placed once the geometry is scaled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    /// The minimum corner of the combined bounding box
+    BoundingBox,
+    /// The start of the baseline of the first line, so texts with and without descenders line up
+    Baseline,
+    /// The center of the combined bounding box
+    Center,
+}
+
+/// Metrics of the laid out text, in output units along the same axes as the geometry
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TextMetrics {
+    /// Distance from the baseline to the top of the tallest glyphs of the font
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the deepest glyphs of the font, as a positive
+    /// length
+    pub descent: f32,
+    /// Extra spacing the font recommends between lines
+    pub line_gap: f32,
+    /// Advance width of the widest line
+    pub advance: f32,
+}
+
+/// The result of [`render_text`]
+#[derive(Debug)]
+pub struct Rendering {
+    pub shapes: Vec<Shape>,
+    pub metrics: TextMetrics,
+    /// Start of the baseline of every laid out line, transformed along with the geometry. In
+    /// vertical text the top of the centre line of every column
+    pub baselines: Vec<Point>,
+    /// Problems that did not stop the rendering, in the order they came up
+    pub warnings: Vec<Warning>,
+    /// Scale applied along X and Y to meet [`RenderOptions::target_size`]
+    pub target_scale: Option<(f32, f32)>,
+    /// Size of the em square in output units, once every scaling is applied
+    pub effective_font_size: f32,
+    /// Number of vertices before and after [`RenderOptions::simplify`]
+    pub simplified_vertices: Option<(usize, usize)>,
+    /// Number of contours dropped by [`RenderOptions::min_contour_area`]
+    pub dropped_contours: usize,
+}
+
+/// A laid out glyph, positioned in the output coordinate space
+struct Symbol {
+    x: i32,
+    y: i32,
+    cache_key: CacheKey,
+    /// The text of the cluster the glyph was shaped from
+    text: String,
+    /// Layout box in pixels, when requested
+    layout_box: Option<LayoutBox>,
+    /// Index of the laid out line the glyph is on
+    line: usize,
+    /// Byte range of the cluster in the text, see [`Shape::source_range`]
+    source_range: (usize, usize),
+}
+
+/// Applies `f` to every point of every shape and to the `anchors`
+fn map_all(shapes: Vec<Shape>, anchors: &mut [Point], f: impl Fn(Point) -> Point) -> Vec<Shape> {
+    for anchor in anchors.iter_mut() {
+        *anchor = f(anchor.clone());
+    }
+    shapes
+        .into_iter()
+        .map(|shape| shape.map_points(&f))
+        .collect()
+}
+
+/// Scales the contours of every glyph into the 0..1 box of the glyph, see [`Point::map_scale`].
+/// Shapes without primitives are kept where they are
+fn normalize_glyphs(shapes: Vec<Shape>) -> Vec<Shape> {
+    let mut boxes: HashMap<usize, (Point, Point)> = HashMap::new();
+    for shape in shapes.iter() {
+        let Some((min, max)) = shape.get_bb() else {
+            continue;
+        };
+        boxes
+            .entry(shape.index)
+            .and_modify(|(glyph_min, glyph_max)| {
+                *glyph_min = glyph_min.min(&min);
+                *glyph_max = glyph_max.max(&max);
+            })
+            .or_insert((min, max));
+    }
+    shapes
+        .into_iter()
+        .map(|shape| match boxes.get(&shape.index) {
+            Some((min, max)) if !shape.primitives.is_empty() => {
+                shape.map_points(|p| p.map_scale(min, max))
+            }
+            _ => shape,
+        })
+        .collect()
+}
+
+/// Width of the widest laid out line across the outlines of its glyphs, so neither the padding
+/// of the box nor the spread of the lines, such as the columns of vertical text, counts towards
+/// it. The plate with `plate_index` belongs to no line and is left out
+fn widest_line(shapes: &[Shape], plate_index: Option<usize>) -> Option<f32> {
+    let mut extents: HashMap<usize, (f32, f32)> = HashMap::new();
+    for shape in shapes
+        .iter()
+        .filter(|shape| Some(shape.index) != plate_index)
+    {
+        if let Some((min_point, max_point)) = shape.get_bb() {
+            let extent = extents
+                .entry(shape.line)
+                .or_insert((min_point.0, max_point.0));
+            *extent = (extent.0.min(min_point.0), extent.1.max(max_point.0));
+        }
+    }
+    extents
+        .values()
+        .map(|(min, max)| max - min)
+        .reduce(f32::max)
+}
+
+/// A rectangle spanning the two corners, running from the lower left corner up to the upper
+/// left one. That is clockwise with Y pointing up, as the output does before `flip_y`, and so
+/// the winding TrueType fonts give their outer contours. The glyphs inside read as holes under
+/// the even-odd rule, for the nonzero rule the windings are corrected once the glyphs are nested
+fn plate(min_point: &Point, max_point: &Point, index: usize) -> Shape {
+    let corners = [
+        min_point.clone(),
+        Point(min_point.0, max_point.1),
+        max_point.clone(),
+        Point(max_point.0, min_point.1),
+    ];
+    let primitives = corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(start, end)| Primitive::Line(start.clone(), end.clone()))
+        .collect();
+    Shape::new(primitives, String::new(), index, 0)
+}
+
+/// Horizontal shift of every glyph of a layout run that undoes the kerning applied while
+/// shaping. Each glyph is moved to where the nominal advances of the glyphs to its left put it,
+/// zero-width glyphs such as combining marks keep their advance of zero
+fn unkerned_offsets(font_system: &mut FontSystem, glyphs: &[LayoutGlyph]) -> Vec<f32> {
+    let deltas: Vec<f32> = glyphs
+        .iter()
+        .map(|glyph| match font_system.get_font(glyph.font_id) {
+            Some(font) if glyph.w != 0.0 => {
+                let nominal = font
+                    .as_swash()
+                    .glyph_metrics(&[])
+                    .scale(glyph.font_size)
+                    .advance_width(glyph.glyph_id);
+                nominal - glyph.w
+            }
+            _ => 0.0,
+        })
+        .collect();
+
+    // Glyphs are stored in logical order, which runs right to left within RTL text
+    let mut visual_order: Vec<usize> = (0..glyphs.len()).collect();
+    visual_order.sort_by(|&a, &b| glyphs[a].x.total_cmp(&glyphs[b].x));
+
+    let mut offsets = vec![0.0; glyphs.len()];
+    let mut shift = 0.0;
+    for i in visual_order {
+        offsets[i] = shift;
+        shift += deltas[i];
+    }
+    offsets
+}
+
+/// Horizontal shift of every glyph of a layout run by the letter spacing of the clusters to its
+/// left, leaving out the glyphs `skip` matches. Glyphs of one cluster, such as a base and its
+/// combining marks, share the same shift
+fn spacing_offsets(
+    glyphs: &[LayoutGlyph],
+    letter_spacing: f32,
+    skip: impl Fn(&LayoutGlyph) -> bool,
+) -> Vec<f32> {
+    // Left edge of every cluster, keyed by the start of its text
+    let mut clusters: Vec<(usize, f32)> = vec![];
+    for glyph in glyphs.iter().filter(|glyph| !skip(glyph)) {
+        match clusters.iter_mut().find(|(start, _)| *start == glyph.start) {
+            Some((_, x)) => *x = x.min(glyph.x),
+            None => clusters.push((glyph.start, glyph.x)),
+        }
+    }
+    // Glyphs are stored in logical order, which runs right to left within RTL text
+    clusters.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let columns: HashMap<usize, usize> = clusters
+        .iter()
+        .enumerate()
+        .map(|(column, (start, _))| (*start, column))
+        .collect();
+
+    glyphs
+        .iter()
+        .map(|glyph| columns.get(&glyph.start).copied().unwrap_or(0) as f32 * letter_spacing)
+        .collect()
+}
+
+/// Warns when none of the faces picked during shaping have the requested weight
+fn warn_if_weight_missing(
+    font_system: &FontSystem,
+    used_fonts: &HashSet<ID>,
+    weight: Weight,
+    warnings: &mut Vec<Warning>,
+) {
+    let mismatched = used_fonts
+        .iter()
+        .filter_map(|id| font_system.db().face(*id))
+        .filter(|face| face.weight != weight);
+
+    for face in mismatched {
+        warnings.push(Warning::MissingWeight {
+            font: face.post_script_name.clone(),
+            requested: weight.0,
+            used: face.weight.0,
+        });
+    }
+}
+
+/// Warns about faces picked during shaping that do not have the requested style, returning them
+fn warn_if_style_missing(
+    font_system: &FontSystem,
+    used_fonts: &HashSet<ID>,
+    style: Style,
+    warnings: &mut Vec<Warning>,
+) -> HashSet<ID> {
+    let mut fallbacks = HashSet::new();
+    for face in used_fonts
+        .iter()
+        .filter_map(|id| font_system.db().face(*id))
+    {
+        if face.style != style {
+            warnings.push(Warning::MissingStyle {
+                font: face.post_script_name.clone(),
+                requested: style,
+                used: face.style,
+            });
+            fallbacks.insert(face.id);
+        }
+    }
+    fallbacks
+}
+
+/// The faces of a font file loaded with [`load_font_file`]
+#[derive(Clone, Debug)]
+pub struct FontFile {
+    /// The face at the requested index
+    pub face: FaceInfo,
+    /// Every face of the file in order, more than one for collections
+    pub faces: Vec<FaceInfo>,
+}
+
+/// Loads every face of the font at `path` into the database, selecting the one at `index`.
+///
+/// Collections (.ttc) contain several faces, single fonts (.ttf, .otf) only the one at index 0.
+/// Without an index the first face is selected
+pub fn load_font_file(
+    font_system: &mut FontSystem,
+    path: &str,
+    index: Option<usize>,
+) -> Result<FontFile, Error> {
+    let data = std::fs::read(path).map_err(|source| Error::Io {
+        context: format!("unable to read font file {path}"),
+        source,
+    })?;
+    let ids = font_system
+        .db_mut()
+        .load_font_source(Source::Binary(Arc::new(data)));
+
+    if ids.is_empty() {
+        return Err(Error::InvalidFont {
+            path: path.to_string(),
+        });
+    }
+
+    let faces = ids
+        .iter()
+        .map(|id| {
+            font_system
+                .db()
+                .face(*id)
+                .cloned()
+                .ok_or_else(|| Error::FontNotFound(path.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let index = index.unwrap_or(0);
+    let face = faces
+        .get(index)
+        .cloned()
+        .ok_or_else(|| Error::FontIndexOutOfRange {
+            path: path.to_string(),
+            index,
+            count: faces.len(),
+        })?;
+
+    Ok(FontFile { face, faces })
+}
+
+/// Loads every font found in the directory at `path` and its subdirectories into the database,
+/// returning how many faces were added. Files that are not fonts are skipped
+pub fn load_font_dir(font_system: &mut FontSystem, path: &str) -> Result<usize, Error> {
+    std::fs::read_dir(path).map_err(|source| Error::Io {
+        context: format!("unable to read font directory {path}"),
+        source,
+    })?;
+    let before = font_system.db().len();
+    font_system.db_mut().load_fonts_dir(path);
+    Ok(font_system.db().len() - before)
+}
+
+/// Scales the outline of a glyph with swash directly, at the given variation coordinates and
+/// preferring the merged layers of a color glyph over its monochrome outline when `color` is set.
+/// cosmic-text always uses the default instance of a variable font and the monochrome outline.
+///
+/// Returns the outline together with whether it was merged from color layers.
+fn scaled_outline_commands(
+    font_system: &mut FontSystem,
+    context: &mut ScaleContext,
+    key: CacheKey,
+    variations: &[([u8; 4], f32)],
+    color: bool,
+) -> Result<(Vec<Command>, bool), Error> {
+    let no_outline = Error::NoOutline {
+        glyph_id: key.glyph_id,
+    };
+    let font = font_system.get_font(key.font_id).ok_or(no_outline)?;
+    let font = font.as_swash();
+
+    for (tag, _) in variations {
+        if !font
+            .variations()
+            .any(|axis| axis.tag() == swash::tag_from_bytes(tag))
+        {
+            let name = font_system
+                .db()
+                .face(key.font_id)
+                .map_or_else(String::new, |face| face.post_script_name.clone());
+            return Err(Error::UnknownVariationAxis {
+                axis: String::from_utf8_lossy(tag).into_owned(),
+                font: name,
+            });
+        }
+    }
+
+    let mut scaler = context
+        .builder(font)
+        .size(f32::from_bits(key.font_size_bits))
+        .variations(variations)
+        .build();
+    let color_outline = if color {
+        scaler.scale_color_outline(key.glyph_id)
+    } else {
+        None
+    };
+    let merged = color_outline.is_some();
+    let outline = color_outline
+        .or_else(|| scaler.scale_outline(key.glyph_id))
+        .or_else(|| scaler.scale_color_outline(key.glyph_id))
+        .ok_or(Error::NoOutline {
+            glyph_id: key.glyph_id,
+        })?;
+    let commands = outline.path().commands().collect();
+    Ok((commands, merged))
+}
+
+/// Converts the outline commands of a glyph into primitives, one list per contour. Closes
+/// become [`Primitive::Close`] if `keep_close` is set, and lines otherwise
+fn outline_to_contours(
+    commands: &[Command],
+    glyph_id: u16,
+    keep_close: bool,
+) -> Result<Vec<Vec<Primitive>>, Error> {
+    let malformed = |reason| Error::MalformedOutline { glyph_id, reason };
+
+    let mut last_point: Option<Point> = None;
+    let mut first_point: Option<Point> = None;
+
+    let mut contours = vec![];
+    let mut primitives = vec![];
+
+    for command in commands {
+        match *command {
+            Command::MoveTo(end_point) => {
+                if !primitives.is_empty() {
+                    contours.push(std::mem::take(&mut primitives));
+                }
+                first_point = Some(Point(end_point.x, end_point.y));
+                last_point = Some(Point(end_point.x, end_point.y))
+            }
+            Command::QuadTo(ctrl_point0, end_point) => {
+                let from_point =
+                    last_point.ok_or_else(|| malformed("QuadTo without a previous point"))?;
+                let end_point = Point(end_point.x, end_point.y);
+                primitives.push(Primitive::Quadratic(
+                    from_point,
+                    Point(ctrl_point0.x, ctrl_point0.y),
+                    end_point.clone(),
+                ));
+                last_point = Some(end_point);
+            }
+            Command::CurveTo(ctrl_point0, ctrl_point1, end_point) => {
+                let from_point =
+                    last_point.ok_or_else(|| malformed("CurveTo without a previous point"))?;
+                let end_point = Point(end_point.x, end_point.y);
+                primitives.push(Primitive::Bezier(
+                    from_point,
+                    Point(ctrl_point0.x, ctrl_point0.y),
+                    Point(ctrl_point1.x, ctrl_point1.y),
+                    end_point.clone(),
+                ));
+                last_point = Some(end_point);
+            }
+            Command::LineTo(end_point) => {
+                let from_point =
+                    last_point.ok_or_else(|| malformed("LineTo without a previous point"))?;
+                let end_point = Point(end_point.x, end_point.y);
+                primitives.push(Primitive::Line(from_point, end_point.clone()));
+                last_point = Some(end_point);
+            }
+            Command::Close => {
+                let from_point =
+                    last_point.ok_or_else(|| malformed("Close without a previous point"))?;
+                // A contour following without a MoveTo starts where this one closes, so the
+                // starting point is kept for it
+                let end_point = first_point
+                    .clone()
+                    .ok_or_else(|| malformed("Close without a starting point"))?;
+                primitives.push(if keep_close {
+                    Primitive::Close(from_point, end_point.clone())
+                } else {
+                    Primitive::Line(from_point, end_point.clone())
+                });
+                contours.push(std::mem::take(&mut primitives));
+                last_point = Some(end_point);
+            }
+        }
+    }
+
+    if !primitives.is_empty() {
+        contours.push(primitives);
+    }
+
+    if contours
+        .iter()
+        .flatten()
+        .flat_map(Primitive::points)
+        .any(|point| !point.is_finite())
+    {
+        return Err(malformed("infinite or NaN coordinate"));
+    }
+    Ok(contours)
+}
+
+/// Shapes and lays out `text`, returning one [`Shape`] per glyph contour.
+///
+/// Warnings are discarded, [`render_text`] returns them along with the shapes.
+pub fn text_to_shapes(
+    text: &str,
+    opts: &RenderOptions,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) -> Result<Vec<Shape>, Error> {
+    render_text(text, opts, font_system, swash_cache).map(|rendering| rendering.shapes)
+}
+
+/// Flattening tolerance of the union when none is given, relative to the longest side of the
+/// geometry
+const UNION_TOLERANCE: f32 = 1e-4;
+
+/// Sorts the contours of every glyph by the minimum corner of their bounding box, X first, and
+/// renumbers them in that order
+fn sort_contours(shapes: &mut [Shape]) {
+    let corner = |shape: &Shape| match (shape.get_bb(), shape.origin.as_ref()) {
+        (Some((min, _)), _) => min,
+        (None, Some(origin)) => origin.clone(),
+        (None, None) => Point(0.0, 0.0),
+    };
+    shapes.sort_by(|a, b| {
+        let (a_corner, b_corner) = (corner(a), corner(b));
+        a.index
+            .cmp(&b.index)
+            .then(a_corner.0.total_cmp(&b_corner.0))
+            .then(a_corner.1.total_cmp(&b_corner.1))
+    });
+    let mut last_index = None;
+    let mut contour = 0;
+    for shape in shapes.iter_mut() {
+        if last_index != Some(shape.index) {
+            last_index = Some(shape.index);
+            contour = 0;
+        }
+        shape.contour = contour;
+        contour += 1;
+    }
+}
+
+/// Splits the text into spans using the family of `attrs` where its font has a glyph for a
+/// character, and otherwise the first of `fallbacks` that has one. Characters without a glyph in
+/// any of them keep the family of `attrs`, leaving the remaining fallback to cosmic-text.
+/// Whitespace and combining marks stay in the span of the character before them, so a base and
+/// its marks are shaped together.
+///
+/// cosmic-text only uses a family with exactly the requested weight and style, so the spans of a
+/// fallback request the face of it closest to `attrs`
+fn fallback_spans<'a>(
+    font_system: &mut FontSystem,
+    text: &'a str,
+    fallbacks: &'a [String],
+    attrs: Attrs<'a>,
+) -> Vec<(&'a str, Attrs<'a>)> {
+    let candidates: Vec<Attrs> = std::iter::once(attrs)
+        .chain(
+            fallbacks
+                .iter()
+                .map(|family| attrs.family(Family::Name(family))),
+        )
+        .collect();
+    let faces: Vec<Option<FaceInfo>> = candidates
+        .iter()
+        .map(|candidate| {
+            let db = font_system.db();
+            db.query(&fontdb::Query {
+                families: &[candidate.family],
+                weight: candidate.weight,
+                stretch: candidate.stretch,
+                style: candidate.style,
+            })
+            .and_then(|id| db.face(id))
+            .cloned()
+        })
+        .collect();
+    let fonts: Vec<_> = faces
+        .iter()
+        .map(|face| face.as_ref().and_then(|face| font_system.get_font(face.id)))
+        .collect();
+    let family_for = |c: char| {
+        fonts
+            .iter()
+            .position(|font| {
+                font.as_ref()
+                    .is_some_and(|font| font.as_swash().charmap().map(c) != 0)
+            })
+            .unwrap_or(0)
+    };
+
+    let mut spans: Vec<(usize, usize)> = vec![];
+    for (i, c) in text.char_indices() {
+        match spans.last_mut() {
+            Some(_) if c.is_whitespace() || is_combining_mark(c) => {}
+            Some((_, family)) if *family == family_for(c) => {}
+            _ => spans.push((i, family_for(c))),
+        }
+    }
+    spans
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, family))| {
+            let end = spans.get(i + 1).map_or(text.len(), |&(end, _)| end);
+            let span_attrs = match &faces[family] {
+                Some(face) if family > 0 => candidates[family]
+                    .weight(face.weight)
+                    .style(face.style)
+                    .stretch(face.stretch),
+                _ => candidates[family],
+            };
+            (&text[start..end], span_attrs)
+        })
+        .collect()
+}
+
+/// Like [`text_to_shapes`], additionally returning the metrics of the font and the laid out text.
+///
+/// The metrics are scaled along with the geometry but are not rotated.
+pub fn render_text(
+    text: &str,
+    opts: &RenderOptions,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) -> Result<Rendering, Error> {
+    // Text metrics indicate the font size and line height of a buffer
+    let line_height = opts.line_height.unwrap_or(opts.font_size * 1.4);
+    let metrics = Metrics::new(opts.font_size, line_height);
+
+    // A Buffer provides shaping and layout for a UTF-8 string, create one per text widget
+    let mut buffer = Buffer::new(font_system, metrics);
+    buffer.set_tab_width(font_system, opts.tab_width);
+
+    // Attributes indicate what font to choose
+    let mut attrs = Attrs::new();
+    if let Some(family) = opts.font_family.as_deref() {
+        attrs = attrs.family(Family::Name(family));
+    }
+    if let Some(weight) = opts.weight {
+        attrs = attrs.weight(weight);
+    }
+    if let Some(style) = opts.style {
+        attrs = attrs.style(style);
+    }
+    if let Some(stretch) = opts.stretch {
+        attrs = attrs.stretch(stretch);
+    }
+
+    if opts.script != Script::Normal {
+        attrs = attrs.metrics(Metrics::new(opts.font_size * Script::SIZE, line_height));
+    }
+    // Glyphs are positioned and extracted at the render size times the scale factor, in pixels
+    // of which the positions below are given, and scaled back down by the render size when placed
+    let oversample = opts.render_size.map_or(1.0, |size| size / opts.font_size);
+    let physical_scale = opts.scale_factor * oversample;
+    let baseline_shift =
+        (opts.script.baseline_shift() * opts.font_size * physical_scale).round() as i32;
+
+    let text: String = match opts.normalize_unicode {
+        Some(NormalizationForm::Nfc) => text.nfc().collect(),
+        Some(NormalizationForm::Nfd) => text.nfd().collect(),
+        None => text.to_string(),
+    };
+    // Byte offsets of the range, which the source ranges of the shapes are given in
+    let byte_range = opts.range.as_ref().map(|range| {
+        let offset = |index: usize| {
+            text.char_indices()
+                .nth(index)
+                .map_or(text.len(), |(i, _)| i)
+        };
+        offset(range.start)..offset(range.end)
+    });
+    // cosmic-text splits the text into paragraphs wherever the bidi algorithm does, at "\n",
+    // "\r", "\r\n" and the other paragraph separators. Byte offset of every paragraph in the
+    // text, which the direction marks are placed at
+    let paragraph_starts: Vec<usize> = BidiParagraphs::new(&text)
+        .map(|paragraph| paragraph.as_ptr() as usize - text.as_ptr() as usize)
+        .collect();
+    let mark = opts.base_direction.mark();
+    let text = match mark {
+        Some(mark) => {
+            let mut marked = String::with_capacity(text.len());
+            let mut end = 0;
+            for &start in paragraph_starts.iter() {
+                marked.push_str(&text[end..start]);
+                marked.push(mark);
+                end = start;
+            }
+            marked.push_str(&text[end..]);
+            marked
+        }
+        None => text,
+    };
+
+    let spans = if opts.fallback_families.is_empty() {
+        vec![(text.as_str(), attrs)]
+    } else {
+        fallback_spans(font_system, &text, &opts.fallback_families, attrs)
+    };
+
+    // Borrow buffer together with the font system for more convenient method calls
+    let mut borrowed = buffer.borrow_with(font_system);
+
+    // Set a size for the text buffer, in pixels. The height is left unbounded so no line is clipped
+    borrowed.set_size(opts.wrap_width, None);
+
+    // Add some text!
+    borrowed.set_rich_text(spans, attrs, opts.shaping);
+
+    if let Some(align) = opts.align {
+        for line in borrowed.lines.iter_mut() {
+            line.set_align(Some(align));
+        }
+    }
+
+    // Perform shaping as desired
+    borrowed.shape_until_scroll(true);
+
+    // Without a wrap width cosmic-text aligns every paragraph within its own width, so the
+    // buffer is narrowed to the widest line and laid out again to align the lines to each other
+    if opts.align.is_some() && opts.wrap_width.is_none() {
+        let widest = borrowed
+            .layout_runs()
+            .map(|run| run.line_w)
+            .fold(0.0, f32::max);
+        borrowed.set_size(Some(widest), None);
+        borrowed.shape_until_scroll(true);
+    }
+    let mut symbols: Vec<Symbol> = vec![];
+    let mut used_fonts = HashSet::new();
+    let mut primary_font = None;
+    let mut baselines = vec![];
+    let mut advance: f32 = 0.0;
+    let mut missing = vec![];
+
+    // Glyph offsets within a paragraph count the direction mark at its start
+    let mark_len = mark.map_or(0, char::len_utf8);
+
+    for (line, run) in buffer.layout_runs().enumerate() {
+        // Vertical text is laid out horizontally first, then every line is turned into a column,
+        // progressing from right to left
+        let column_x = -(line as f32) * (line_height + opts.line_gap);
+        let line_y = if opts.vertical {
+            0
+        } else {
+            ((run.line_y + line as f32 * opts.line_gap) * physical_scale) as i32
+        };
+        // Glyphs are placed on whole pixels, see the vertical offset below
+        baselines.push(if opts.vertical {
+            Point((column_x + opts.font_size / 2.0) * opts.scale_factor, 0.0)
+        } else {
+            Point(0.0, -line_y as f32 / oversample)
+        });
+        // Top of the em box of the current cluster, and its shift onto the column and advance
+        // height, in vertical text
+        let mut pen_y = 0.0;
+        let mut column_shift = (0.0, 0.0);
+        let mut cluster_height = 0.0;
+        let mut last_cluster = None;
+        let kerning = if opts.kerning {
+            vec![0.0; run.glyphs.len()]
+        } else {
+            unkerned_offsets(font_system, run.glyphs)
+        };
+        // The direction mark only steers the bidi algorithm and must not count as a glyph
+        let is_mark = |glyph: &LayoutGlyph| {
+            glyph.start == 0 && mark.is_some_and(|mark| glyph.end == mark.len_utf8())
+        };
+        // cosmic-text has no letter spacing, so every cluster is shifted by the spacing of the
+        // clusters to its left instead
+        let spacing = spacing_offsets(run.glyphs, opts.letter_spacing, is_mark);
+        for ((glyph, kerning), spacing) in run.glyphs.iter().zip(kerning).zip(spacing) {
+            if is_mark(glyph) {
+                continue;
+            }
+            // Control characters are never drawn, so they are left out instead of reporting them
+            // as missing and drawing the placeholder box. Tabs and line breaks are laid out by
+            // cosmic-text already
+            if run.text[glyph.start..glyph.end]
+                .chars()
+                .all(|c| c.is_control() && !c.is_whitespace())
+            {
+                continue;
+            }
+            let cluster_start = last_cluster != Some(glyph.start);
+            if last_cluster.is_some() && cluster_start {
+                pen_y += cluster_height + opts.letter_spacing;
+            }
+            last_cluster = Some(glyph.start);
+
+            // Glyph 0 is the .notdef placeholder fonts draw for characters they do not cover
+            if glyph.glyph_id == 0 {
+                let column =
+                    run.text[..glyph.start].chars().count() + 1 - usize::from(mark.is_some());
+                missing.push(format!(
+                    "{:?} at line {}, column {column}",
+                    &run.text[glyph.start..glyph.end],
+                    run.line_i + 1
+                ));
+            }
+            used_fonts.insert(glyph.font_id);
+            primary_font.get_or_insert(glyph.font_id);
+            advance = advance.max(glyph.x + glyph.w + spacing + kerning);
+            // Every cluster of vertical text is centred on its column, with the top of its em
+            // box at the pen. The glyphs of a cluster share the shift of its first glyph, so
+            // marks keep their place relative to the base
+            if opts.vertical && cluster_start {
+                let font = font_system.get_font(glyph.font_id);
+                let (ascent, height) = font.map_or((glyph.font_size, glyph.font_size), |font| {
+                    let font = font.as_swash();
+                    (
+                        font.metrics(&[]).scale(glyph.font_size).ascent,
+                        font.glyph_metrics(&[])
+                            .scale(glyph.font_size)
+                            .advance_height(glyph.glyph_id),
+                    )
+                });
+                let x = column_x + (glyph.font_size - glyph.w) / 2.0;
+                column_shift = (x - glyph.x, pen_y + ascent);
+                cluster_height = height;
+            }
+            let (pen_x, offset) = if opts.vertical {
+                (
+                    glyph.x + column_shift.0,
+                    (column_shift.0, column_shift.1 - glyph.y),
+                )
+            } else {
+                (glyph.x + spacing + kerning, (spacing + kerning, 0.0))
+            };
+            let physical_glyph = glyph.physical(
+                (offset.0 * physical_scale, offset.1 * physical_scale),
+                physical_scale,
+            );
+
+            // Layout positions grow downwards while outlines grow upwards, so the vertical
+            // offset is negated to stack subsequent lines below the previous ones
+            let x = physical_glyph.x;
+            let y = -(line_y + physical_glyph.y) + baseline_shift;
+
+            let layout_box = opts.layout_boxes.then(|| {
+                let font_metrics = font_system
+                    .get_font(glyph.font_id)
+                    .map(|font| font.as_swash().metrics(&[]))
+                    .unwrap_or_default();
+                let units = glyph.font_size / f32::from(font_metrics.units_per_em.max(1));
+                LayoutBox {
+                    pen: Point(
+                        pen_x * opts.scale_factor,
+                        if opts.vertical {
+                            -column_shift.1 * opts.scale_factor
+                        } else {
+                            (-line_y + baseline_shift) as f32 / oversample
+                        },
+                    ),
+                    advance: glyph.w,
+                    ascent: font_metrics.ascent * units,
+                    descent: font_metrics.descent * units,
+                }
+            });
+
+            symbols.push(Symbol {
+                x,
+                y,
+                cache_key: physical_glyph.cache_key,
+                text: run.text[glyph.start..glyph.end].to_string(),
+                layout_box,
+                line,
+                source_range: (
+                    paragraph_starts[run.line_i] + glyph.start.saturating_sub(mark_len),
+                    paragraph_starts[run.line_i] + glyph.end - mark_len,
+                ),
+            });
+        }
+    }
+
+    let mut warnings = vec![];
+    if !missing.is_empty() {
+        let missing = missing.join("; ");
+        if opts.strict {
+            return Err(Error::MissingGlyphs(missing));
+        }
+        warnings.push(Warning::MissingGlyphs(missing));
+    }
+    if let Some(weight) = opts.weight {
+        warn_if_weight_missing(font_system, &used_fonts, weight, &mut warnings);
+    }
+    let style_fallbacks = match opts.style {
+        Some(style) => warn_if_style_missing(font_system, &used_fonts, style, &mut warnings),
+        None => HashSet::new(),
+    };
+
+    // Glyphs repeat a lot in typical text, so each outline is converted once, untransformed, and
+    // only its points are transformed for every occurrence. Fetching outlines needs the font
+    // system mutably, so it happens up front and the placement below can run in parallel
+    let mut templates: HashMap<CacheKey, Vec<Vec<Primitive>>> = HashMap::new();
+    let mut scale_context = ScaleContext::new();
+    let mut merged_color = false;
+    for symbol in symbols.iter() {
+        let key = symbol.cache_key;
+        if !opts.reuse_outlines || !templates.contains_key(&key) {
+            let commands = if opts.variations.is_empty() && !opts.flatten_color {
+                swash_cache
+                    .get_outline_commands(font_system, key)
+                    .map(<[Command]>::to_vec)
+                    .ok_or(Error::NoOutline {
+                        glyph_id: key.glyph_id,
+                    })
+            } else {
+                scaled_outline_commands(
+                    font_system,
+                    &mut scale_context,
+                    key,
+                    &opts.variations,
+                    opts.flatten_color,
+                )
+                .map(|(commands, merged)| {
+                    merged_color |= merged;
+                    commands
+                })
+            };
+            let contours = match commands {
+                Ok(commands) => outline_to_contours(&commands, key.glyph_id, opts.keep_close)?,
+                // Bitmap-only glyphs, such as those of some emoji fonts, have no outline at all
+                Err(Error::NoOutline { glyph_id }) if !opts.strict => {
+                    warnings.push(Warning::NoOutline {
+                        glyph_id,
+                        text: symbol.text.clone(),
+                    });
+                    vec![]
+                }
+                Err(error) => return Err(error),
+            };
+            templates.insert(key, contours);
+        }
+    }
+
+    if merged_color {
+        warnings.push(Warning::MergedColorLayers);
+    }
+
+    let place = |(index, symbol): (usize, Symbol)| -> Vec<Shape> {
+        let key = symbol.cache_key;
+        let mut transform = Transform::translation(symbol.x as f32, symbol.y as f32);
+        if let Some(angle) = opts.shear {
+            transform = Transform::skew(Angle::from_degrees(angle), Angle::from_degrees(0.0))
+                .then(&transform);
+        }
+        if let Some(angle) = opts.synthetic_italic {
+            if style_fallbacks.contains(&key.font_id) {
+                transform = Transform::skew(Angle::from_degrees(angle), Angle::from_degrees(0.0))
+                    .then(&transform);
+            }
+        }
+        transform = transform.then(&Transform::scale(1.0 / oversample, 1.0 / oversample));
+
+        let template = &templates[&key];
+        if template.is_empty() && opts.emit_empty {
+            return vec![Shape {
+                origin: Some(Point(0.0, 0.0).transform(&transform)),
+                layout_box: symbol.layout_box,
+                line: symbol.line,
+                source_range: Some(symbol.source_range),
+                ..Shape::new(vec![], symbol.text, index, 0)
+            }];
+        }
+
+        template
+            .iter()
+            .enumerate()
+            .map(|(contour, primitives)| {
+                let primitives = primitives
+                    .iter()
+                    .cloned()
+                    .map(|primitive| primitive.map_points(|p| p.transform(&transform)))
+                    .collect();
+                Shape {
+                    layout_box: symbol.layout_box.clone(),
+                    line: symbol.line,
+                    source_range: Some(symbol.source_range),
+                    ..Shape::new(primitives, symbol.text.clone(), index, contour)
+                }
+            })
+            .collect()
+    };
+
+    #[cfg(feature = "rayon")]
+    let mut shapes: Vec<Shape> = {
+        use rayon::prelude::*;
+        symbols
+            .into_par_iter()
+            .enumerate()
+            .flat_map_iter(place)
+            .collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let mut shapes: Vec<Shape> = symbols.into_iter().enumerate().flat_map(place).collect();
+
+    // The pen position at the start of every baseline follows every transformation, so the
+    // origin can be moved onto the first one at the end
+    if let Some(angle) = opts.rotate {
+        if let Some((min_point, max_point)) = bounding_box(&shapes) {
+            let (center_x, center_y) = (
+                (min_point.0 + max_point.0) / 2.0,
+                (min_point.1 + max_point.1) / 2.0,
+            );
+            let rotation = Transform::translation(-center_x, -center_y)
+                .then(&Transform::rotation(Angle::from_degrees(angle)))
+                .then(&Transform::translation(center_x, center_y));
+            shapes = map_all(shapes, &mut baselines, |p| p.transform(&rotation));
+        }
+    }
+    if opts.mirror_x || opts.mirror_y {
+        let (scale_x, scale_y) = (
+            if opts.mirror_x { -1.0 } else { 1.0 },
+            if opts.mirror_y { -1.0 } else { 1.0 },
+        );
+        shapes = map_all(shapes, &mut baselines, |p| {
+            Point(p.0 * scale_x, p.1 * scale_y)
+        });
+    }
+    if opts.flip_y {
+        shapes = map_all(shapes, &mut baselines, |p| Point(p.0, -p.1));
+    }
+    // Lengths are tracked separately from the shapes, so the metrics can follow the scaling
+    let mut scale = (opts.scale_factor, opts.scale_factor);
+    // The box the target size applies to, when padding makes it larger than the geometry
+    let mut padded_box = None;
+    if opts.normalize {
+        let (min_point, max_point) = bounding_box(&shapes).ok_or(Error::EmptyGeometry)?;
+        let range = f32::max(max_point.0 - min_point.0, max_point.1 - min_point.1);
+        scale = (scale.0 / range, scale.1 / range);
+        shapes = map_all(shapes, &mut baselines, |p| {
+            p.map_scale(&min_point, &max_point)
+        });
+        if opts.per_glyph_normalize {
+            shapes = normalize_glyphs(shapes);
+        }
+        if let Some(pad) = opts.bbox_pad {
+            let factor = 1.0 - 2.0 * pad;
+            scale = (scale.0 * factor, scale.1 * factor);
+            shapes = map_all(shapes, &mut baselines, |p| {
+                Point(p.0 * factor + pad, p.1 * factor + pad)
+            });
+            let extent = |length: f32| length / range * factor + 2.0 * pad;
+            padded_box = Some((
+                Point(0.0, 0.0),
+                Point(
+                    extent(max_point.0 - min_point.0),
+                    extent(max_point.1 - min_point.1),
+                ),
+            ));
+        }
+    } else if let Some(units) = opts.units {
+        let factor = units.per_point();
+        scale = (scale.0 * factor, scale.1 * factor);
+        shapes = map_all(shapes, &mut baselines, |p| {
+            Point(p.0 * factor, p.1 * factor)
+        });
+    }
+    // The plate is added once the box it fills is known, and transformed along with the glyphs
+    let mut plate_index = None;
+    if opts.invert {
+        let (min_point, max_point) = padded_box
+            .clone()
+            .or_else(|| bounding_box(&shapes))
+            .ok_or(Error::EmptyGeometry)?;
+        let index = shapes
+            .iter()
+            .map(|shape| shape.index + 1)
+            .max()
+            .unwrap_or(0);
+        shapes.push(plate(&min_point, &max_point, index));
+        plate_index = Some(index);
+    }
+    let mut target_scale = None;
+    if let Some(target_size) = opts.target_size {
+        let (min_point, max_point) = padded_box
+            .or_else(|| bounding_box(&shapes))
+            .ok_or(Error::EmptyGeometry)?;
+        let (width, height) = (max_point.0 - min_point.0, max_point.1 - min_point.1);
+        let (scale_x, scale_y) = match target_size {
+            TargetSize::Longest(longest) => {
+                let scale = longest / f32::max(width, height);
+                (scale, scale)
+            }
+            TargetSize::Exact(target_width, target_height) => {
+                (target_width / width, target_height / height)
+            }
+            TargetSize::Width(target_width) => {
+                let factor =
+                    target_width / widest_line(&shapes, plate_index).ok_or(Error::EmptyGeometry)?;
+                (factor, factor)
+            }
+        };
+        target_scale = Some((scale_x, scale_y));
+        scale = (scale.0 * scale_x, scale.1 * scale_y);
+        shapes = map_all(shapes, &mut baselines, |p| {
+            Point(p.0 * scale_x, p.1 * scale_y)
+        });
+    }
+    if let Some(factor) = opts.scale {
+        scale = (scale.0 * factor, scale.1 * factor);
+        shapes = map_all(shapes, &mut baselines, |p| {
+            Point(p.0 * factor, p.1 * factor)
+        });
+    }
+    if let Some(origin) = opts.origin {
+        let (min_point, max_point) = bounding_box(&shapes).ok_or(Error::EmptyGeometry)?;
+        let origin = match origin {
+            Origin::BoundingBox => min_point,
+            Origin::Baseline => baselines.first().cloned().unwrap_or(Point(0.0, 0.0)),
+            Origin::Center => Point(
+                (min_point.0 + max_point.0) / 2.0,
+                (min_point.1 + max_point.1) / 2.0,
+            ),
+        };
+        shapes = map_all(shapes, &mut baselines, |p| {
+            Point(p.0 - origin.0, p.1 - origin.1)
+        });
+    }
+    if let Some((x, y)) = opts.offset {
+        shapes = map_all(shapes, &mut baselines, |p| Point(p.0 + x, p.1 + y));
+    }
+    // Flattening, the union and the winding all assume finite geometry
+    check_finite(&shapes)?;
+    if let Some(byte_range) = byte_range {
+        shapes.retain(|shape| {
+            shape
+                .source_range
+                .is_none_or(|(start, _)| byte_range.contains(&start))
+        });
+    }
+    if opts.cubic_only {
+        shapes = shapes
+            .into_iter()
+            .map(|shape| {
+                shape.map_primitives(|primitives| {
+                    primitives.into_iter().map(Primitive::into_cubic).collect()
+                })
+            })
+            .collect();
+    }
+    // The union works on polygons, so curves are flattened for it even without a tolerance
+    let flatten = opts.flatten.or_else(|| {
+        let (min_point, max_point) = bounding_box(&shapes)?;
+        let longest = f32::max(max_point.0 - min_point.0, max_point.1 - min_point.1);
+        opts.union.then_some(longest * UNION_TOLERANCE)
+    });
+    if let Some(tolerance) = flatten {
+        let mut capped = false;
+        shapes = shapes
+            .into_iter()
+            .map(|shape| {
+                let (shape, shape_capped) =
+                    flatten::flatten_shape(shape, tolerance, opts.max_segments);
+                capped |= shape_capped;
+                shape
+            })
+            .collect();
+        if capped {
+            warnings.push(Warning::SegmentsCapped {
+                max_segments: opts.max_segments,
+                tolerance,
+            });
+        }
+    }
+    if opts.union {
+        // The plate covers every glyph, so it is kept out of the union
+        let (plate, glyphs): (Vec<Shape>, Vec<Shape>) = shapes
+            .into_iter()
+            .partition(|shape| Some(shape.index) == plate_index);
+        shapes = boolean::union_shapes(glyphs);
+        let index = shapes
+            .iter()
+            .map(|shape| shape.index + 1)
+            .max()
+            .unwrap_or(0);
+        plate_index = plate_index.map(|_| index);
+        shapes.extend(plate.into_iter().map(|plate| Shape { index, ..plate }));
+    }
+    let mut simplified_vertices = None;
+    if let Some(epsilon) = opts.simplify {
+        let before: usize = shapes.iter().map(simplify::vertex_count).sum();
+        shapes = shapes
+            .into_iter()
+            .map(|shape| simplify::simplify_shape(shape, epsilon))
+            .collect();
+        let after: usize = shapes.iter().map(simplify::vertex_count).sum();
+        simplified_vertices = Some((before, after));
+    }
+    // Rounding can make neighbouring points coincide, so degenerate primitives are removed
+    // afterwards. Half a unit of the last kept digit tolerates float error on the rounded grid
+    let epsilon = 0.5 / 10f32.powi(opts.precision as i32);
+    let straighten = opts.straighten.unwrap_or(2.0 * epsilon);
+    let mut shapes: Vec<Shape> = shapes
+        .into_iter()
+        .map(|shape| flatten::straighten_shape(shape, straighten))
+        .map(|shape| shape.map_points(|p| p.round(opts.precision)))
+        .map(|shape| clean::clean_shape(shape, epsilon))
+        .filter(|shape| !shape.primitives.is_empty() || shape.origin.is_some())
+        .collect();
+    let mut dropped_contours = 0;
+    if let Some(min_area) = opts.min_contour_area {
+        let before = shapes.len();
+        shapes.retain(|shape| !shape.is_closed() || shape.signed_area().abs() >= min_area);
+        dropped_contours = before - shapes.len();
+    }
+    if opts.fill_rule == FillRule::NonZero {
+        shapes = nest::orient_for_nonzero(shapes);
+        // Each glyph is nested on its own, so inside the plate its outer contours turn into
+        // holes and its holes into islands
+        if plate_index.is_some() {
+            shapes = shapes
+                .into_iter()
+                .map(|shape| {
+                    if Some(shape.index) == plate_index {
+                        return shape;
+                    }
+                    shape.reverse()
+                })
+                .collect();
+        }
+    }
+    if opts.reverse_contours {
+        shapes = shapes.into_iter().map(Shape::reverse).collect();
+    }
+    let mut shapes: Vec<Shape> = shapes
+        .into_iter()
+        .map(Shape::annotate)
+        .map(|shape| Shape {
+            length: opts
+                .lengths
+                .then(|| geometry::round(shape.length(), opts.precision)),
+            stroke: opts.stroke,
+            layout_box: shape.layout_box.map(|layout_box| LayoutBox {
+                advance: geometry::round(layout_box.advance * scale.0, opts.precision),
+                ascent: geometry::round(layout_box.ascent * scale.1, opts.precision),
+                descent: geometry::round(layout_box.descent * scale.1, opts.precision),
+                ..layout_box
+            }),
+            ..shape
+        })
+        .collect();
+    if opts.glyph_boxes {
+        let mut boxes: HashMap<usize, Bounds> = HashMap::new();
+        for shape in shapes.iter() {
+            let Some((min, max)) = shape.get_bb() else {
+                continue;
+            };
+            boxes
+                .entry(shape.index)
+                .and_modify(|bounds| {
+                    bounds.min = bounds.min.min(&min);
+                    bounds.max = bounds.max.max(&max);
+                })
+                .or_insert(Bounds { min, max });
+        }
+        for shape in shapes.iter_mut() {
+            shape.bbox = boxes.get(&shape.index).cloned();
+        }
+    }
+    if opts.stable_order {
+        sort_contours(&mut shapes);
+    }
+
+    let font_metrics = primary_font
+        .and_then(|id| font_system.get_font(id))
+        .map(|font| font.as_swash().metrics(&[]))
+        .ok_or(Error::EmptyGeometry)?;
+    let units = opts.font_size / f32::from(font_metrics.units_per_em);
+    let metrics = TextMetrics {
+        ascent: geometry::round(font_metrics.ascent * units * scale.1, opts.precision),
+        descent: geometry::round(font_metrics.descent * units * scale.1, opts.precision),
+        line_gap: geometry::round(font_metrics.leading * units * scale.1, opts.precision),
+        advance: geometry::round(advance * scale.0, opts.precision),
+    };
+
+    let baselines = baselines
+        .into_iter()
+        .map(|p| p.round(opts.precision))
+        .collect();
+
+    Ok(Rendering {
+        shapes,
+        metrics,
+        baselines,
+        warnings,
+        target_scale,
+        // The em square follows the vertical scale, whatever the font size was in pixels
+        effective_font_size: opts.font_size * scale.1,
+        simplified_vertices,
+        dropped_contours,
+    })
+}
@@ -1,257 +1,1335 @@
-use cosmic_text::{
-    Attrs, Buffer, CacheKey, Color, Command, FontSystem, Metrics, Shaping, SwashCache, Transform,
-};
+use cosmic_text::{Align, FontSystem, Shaping, Style, SwashCache, Weight};
+
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
 
-use clap::Parser;
+use fontdb::{FaceInfo, Source};
+use fonts::{
+    bounding_box, dxf, fcmacro, flatten, gcode, json, nest, svg, BaseDirection, Error, FillRule,
+    NormalizationForm, Origin, Point, RenderOptions, Script, Shape, TargetSize, Units,
+};
+use serde::Deserialize;
+use std::io::{BufRead, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitCode;
 
-/// Simple program to greet a person
+/// Turns text into glyph outlines made of lines and Bezier curves, for CAD programs such as FreeCAD
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// String to parse into text
+    /// String to parse into text, or "-" to read it from stdin. Omitted when --text-file is used
+    text: Option<String>,
+    /// File to write the result to, or "-" to write it to stdout
+    output_file: Option<String>,
+
+    /// List the available font faces, optionally only those whose family contains FILTER, and
+    /// exit. Faces loaded with --font-file are included
+    #[arg(
+        long,
+        value_name = "FILTER",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with_all = ["text", "output_file", "text_file", "batch"]
+    )]
+    list_fonts: Option<String>,
+
+    /// Render every entry of this JSON manifest, an array of {"text", "output"} objects, sharing
+    /// the font database between them. Failed entries are reported without stopping the others
+    #[arg(long, conflicts_with_all = ["text", "output_file", "text_file"])]
+    batch: Option<String>,
+
+    /// Keep running and render one request per line of stdin, "TEXT<TAB>OUTPUT_FILE", sharing
+    /// the font database between them so system fonts are only scanned once. "\n", "\t" and
+    /// "\\" in TEXT stand for a newline, a tab and a backslash. Every request is answered on
+    /// stdout with "ok<TAB>OUTPUT_FILE" or "error<TAB>MESSAGE"
+    #[arg(long, conflicts_with_all = ["text", "output_file", "text_file", "batch", "dry_run"])]
+    serve: bool,
+
+    /// Read the text to render from this UTF-8 file instead of the TEXT argument
+    #[arg(long)]
+    text_file: Option<String>,
+
+    /// Extra space, in pixels, inserted between consecutive characters, e.g. for tool clearance
+    /// when engraving. Not taken into account when wrapping lines
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    letter_spacing: f32,
+
+    /// Place every glyph at its nominal advance, ignoring the kerning of the font, e.g. to line
+    /// characters up on a grid
+    #[arg(long)]
+    no_kerning: bool,
+
+    /// Font size, in pixels, used when shaping the text
+    #[arg(long, default_value_t = 14.0, value_parser = parse_positive)]
+    font_size: f32,
+
+    /// Size, in pixels, the glyphs are positioned and their outlines extracted at before being
+    /// scaled back to the font size. Glyphs are placed on whole pixels, so a size well above the
+    /// font size, such as 1000, avoids their positions snapping at small font sizes
+    #[arg(long, value_parser = parse_positive)]
+    render_size: Option<f32>,
+
+    /// Device pixels per pixel of the font size. The glyphs are laid out at the font size and
+    /// then positioned, snapped to whole device pixels, and extracted at this scale, which the
+    /// geometry keeps: a factor of 2 doubles every coordinate before normalization
+    #[arg(long, value_parser = parse_positive, conflicts_with = "dpi")]
+    scale_factor: Option<f32>,
+
+    /// Resolution the glyphs are positioned at, in device pixels per inch. The font size is taken
+    /// at 96 dpi, so this is the scale factor times 96
+    #[arg(long, value_parser = parse_positive)]
+    dpi: Option<f32>,
+
+    /// Line height, in pixels. Defaults to 1.4 times the font size
+    #[arg(long, value_parser = parse_positive)]
+    line_height: Option<f32>,
+
+    /// Extra spacing, in pixels, added between consecutive lines
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    line_gap: f32,
+
+    /// Wrap lines longer than this width, in pixels. Lines are not wrapped by default
+    #[arg(long, value_parser = parse_positive)]
+    wrap_width: Option<f32>,
+
+    /// Alignment of the lines of multi-line text, within the widest line or within --wrap-width
+    #[arg(long, value_enum)]
+    align: Option<Alignment>,
+
+    /// Unicode normalization applied to the text before shaping, so precomposed and decomposed
+    /// accents produce the same outlines
+    #[arg(long, value_enum, default_value_t = UnicodeForm::Nfc)]
+    normalize_unicode: UnicodeForm,
+
+    /// Only emit the glyphs of the characters START up to END, counted from 0 after Unicode
+    /// normalization, e.g. "0..3" for the first three. Either end may be left out. The whole text
+    /// is shaped and placed, so kerning and ligatures with the characters around the range are
+    /// kept and every glyph stays where it is in the whole text, e.g. for the frames of a reveal
+    #[arg(long, value_name = "START..END", value_parser = parse_range)]
+    range: Option<Range<usize>>,
+
+    /// Write every line top to bottom as a column, the columns following each other from right
+    /// to left, e.g. for CJK signage. Glyphs advance by the vertical metrics of the font and are
+    /// centred on their column, but keep their upright horizontal forms, without rotated or
+    /// vertical alternates. --line-height sets the distance between columns
+    #[arg(long, conflicts_with = "align")]
+    vertical: bool,
+
+    /// Distance between tab stops, in widths of a space at the chosen font. A tab advances to
+    /// the next stop, so tab separated columns line up across lines
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+    tab_width: u16,
+
+    /// Fill rule the consumer uses. nonzero reverses contours as needed so outer contours run
+    /// counter-clockwise and holes clockwise, evenodd keeps the winding of the font
+    #[arg(long, value_enum, default_value_t = FillMode::Evenodd)]
+    fill_rule: FillMode,
+
+    /// Trace every contour the other way round, after --fill-rule, for importers expecting the
+    /// opposite winding of the one produced, which otherwise fill holes and leave glyphs empty
+    #[arg(long)]
+    reverse_contours: bool,
+
+    /// Number of decimal places kept in the output coordinates
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(0..=9))]
+    precision: u32,
+
+    /// Rotate the whole text counter-clockwise by this many degrees around the center of its
+    /// bounding box. The output is normalized to the rotated extent
+    #[arg(long, allow_hyphen_values = true)]
+    rotate: Option<f32>,
+
+    /// Mirror the text along the given axes, e.g. for back-lit signs or reverse glass. x flips
+    /// it left to right, y upside down, xy both
+    #[arg(long, value_enum)]
+    mirror: Option<Mirror>,
+
+    /// Negate every Y coordinate. The output is Y-up by default, matching FreeCAD sketches, this
+    /// produces Y-down coordinates for screen-space consumers instead
+    #[arg(long)]
+    flip_y: bool,
+
+    /// Keep the laid out coordinates instead of normalizing them into a 0..1 box. Coordinates are
+    /// then in pixels at the chosen font size, with the top of the first line at y = 0
+    #[arg(long)]
+    no_normalize: bool,
+
+    /// Normalize every glyph into a 0..1 box of its own instead of the text as a whole, e.g. to
+    /// build a symbol library from several characters at once. The letters lose their relative
+    /// sizes and positions, so this is not meant for laying out text
+    #[arg(long, conflicts_with = "no_normalize")]
+    per_glyph_normalize: bool,
+
+    /// Leave this margin around the normalized output, as a fraction of the 0..1 box, so the
+    /// geometry spans PAD..1-PAD along its longest side. --target-size then sizes the padded box.
+    /// Must be below 0.5
+    #[arg(long, value_name = "PAD", value_parser = parse_padding, conflicts_with = "no_normalize")]
+    bbox_pad: Option<f32>,
+
+    /// Scale the output so its longest side matches the given size, or, when two values are given,
+    /// so its width and height match them exactly. Sizes are in --units, millimetres by default
+    #[arg(long, num_args = 1..=2, value_names = ["WIDTH", "HEIGHT"], value_parser = parse_positive)]
+    target_size: Option<Vec<f32>>,
+
+    /// Scale the output uniformly so the widest line is exactly this wide, in --units, and report
+    /// the effective font size. Unlike --target-size the height never decides the scale, and the
+    /// margin of --bbox-pad is added on top of the width. Lines are wrapped at --wrap-width before
+    /// scaling, so they break the same way at every size
+    #[arg(long, value_name = "WIDTH", value_parser = parse_positive, conflicts_with = "target_size")]
+    fit_width: Option<f32>,
+
+    /// Unit of the output coordinates, recorded in the JSON and DXF output. With --no-normalize
+    /// the font size is taken in points and the coordinates converted into this unit
+    #[arg(long, value_enum)]
+    units: Option<Unit>,
+
+    /// Multiply every coordinate by this factor after normalization and --target-size, before
+    /// rounding. With --no-normalize it scales the raw pixel coordinates
+    #[arg(long, value_parser = parse_positive)]
+    scale: Option<f32>,
+
+    /// Where to put the origin once the output is scaled. baseline puts it at the start of the
+    /// first baseline, so separately generated words with and without descenders line up
+    #[arg(long, value_enum)]
+    origin: Option<OriginMode>,
+
+    /// Put the origin at the center of the bounding box, the same as --origin center, so the
+    /// text can be placed and rotated symmetrically in CAD
+    #[arg(long, conflicts_with = "origin")]
+    center_origin: bool,
+
+    /// Shift every point by X and Y once the output is scaled and the origin placed, e.g. to
+    /// drop the text at a known location in a sketch
+    #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+    offset: Option<Vec<f32>>,
+
+    /// Replace curves with lines deviating at most this far from the curve, in output units
+    #[arg(long, value_parser = parse_positive)]
+    flatten: Option<f32>,
+
+    /// Merge overlapping glyphs, such as the connected letters of script fonts, into a single
+    /// outline per region so no seams are left inside it when extruded. Holes are kept. The output
+    /// is made of lines, flattened with the --flatten tolerance or a ten-thousandth of the size,
+    /// and its shapes no longer carry their characters
+    #[arg(long)]
+    union: bool,
+
+    /// Surround the text with a rectangle and cut the glyphs out of it as holes, e.g. for
+    /// stencils or backlit signs. The rectangle is the bounding box of the glyphs, widened by
+    /// --bbox-pad for a border, and is the last shape, with an empty "char"
+    #[arg(long, conflicts_with = "nest")]
+    invert: bool,
+
+    /// Most lines a single curve is flattened into, whatever the tolerance. Curves needing more
+    /// are flattened only as far as the cap allows, with a warning
+    #[arg(
+        long,
+        default_value_t = flatten::DEFAULT_MAX_SEGMENTS,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    max_segments: usize,
+
+    /// Drop vertices of straight line chains that deviate at most this far from the simplified
+    /// chain, in output units. Curves are kept unless --flatten turns them into lines first
+    #[arg(long, value_parser = parse_positive)]
+    simplify: Option<f32>,
+
+    /// Drop closed contours enclosing less than this area, in output units squared, e.g. specks
+    /// or overshoot slivers some fonts contain that CAD tools fail to turn into faces. Counted
+    /// after normalization, a target size and --units, and reported on stderr
+    #[arg(long, value_name = "AREA", value_parser = parse_positive)]
+    min_contour_area: Option<f32>,
+
+    /// Replace curves whose control points lie less than this far from their chord with lines,
+    /// in output units. Defaults to one unit of the last decimal place kept by --precision, as
+    /// rounding would hide such a curve anyway. 0 keeps every curve
+    #[arg(long, value_name = "DISTANCE", value_parser = parse_non_negative)]
+    straighten: Option<f32>,
+
+    /// Convert every quadratic curve into the equivalent cubic Bezier
+    #[arg(long)]
+    cubic_only: bool,
+
+    /// Keep the close of every contour as a primitive of its own, "Close" in JSON and "Z" in SVG,
+    /// instead of an explicit line back to the start, for consumers closing contours themselves.
+    /// It is kept even when the contour already ends at its start, with zero length
+    #[arg(long)]
+    keep_close: bool,
+
+    /// Draw color glyphs, such as emoji, as the silhouette of all their color layers instead of
+    /// the plain fallback outline of the font. Colors are discarded
+    #[arg(long)]
+    flatten_color: bool,
+
+    /// Fail instead of warning when a character has no glyph in any available font, rather than
+    /// drawing the font's placeholder box, or when a glyph has no outline, such as a bitmap
+    /// emoji, rather than leaving it out
+    #[arg(long)]
+    strict: bool,
+
+    /// Sort the contours of each glyph by the minimum corner of their bounding box, left to right
+    /// then bottom to top, so the output does not depend on the contour order of the font
+    #[arg(long)]
+    stable_order: bool,
+
+    /// Add the layout box of its glyph to every shape: the pen position on the baseline, the
+    /// advance width and the ascent and descent of the font, as "layout_box"
+    #[arg(long)]
+    layout_boxes: bool,
+
+    /// Add the box enclosing its glyph to every shape, as "bbox" with "min" and "max" corners in
+    /// the output coordinates, e.g. to select or lay out single letters
+    #[arg(long)]
+    glyph_bbox: bool,
+
+    /// Add the length of the path along every shape, in output units, as "length", e.g. to
+    /// estimate cutting times
+    #[arg(long)]
+    length: bool,
+
+    /// Emit the contours as strokes to be followed by a pen plotter or engraver rather than as
+    /// filled regions. Each shape is tagged with "stroke" so FreeCAD keeps it as a wire
+    #[arg(long)]
+    stroke: bool,
+
+    /// Include a shape without primitives for every glyph without an outline, such as a space,
+    /// with its pen position as "origin", so the layout can be reconstructed from the output
+    #[arg(long)]
+    emit_empty: bool,
+
+    /// Format of the output file
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+
+    /// Write the JSON output as a bare array of shapes, without the bounding box. Same as
+    /// --schema 1
+    #[arg(long)]
+    legacy_array: bool,
+
+    /// Version of the JSON structure to write, recorded in its "version" field. 1 is the bare
+    /// array of shapes read by old importers, 2 the object without the line grouping, "Close"
+    /// primitives and the "line", "source_range", "length", "layout_box" and "bbox" fields of a
+    /// shape, which version 3 added
+    #[arg(
+        long,
+        default_value_t = json::SCHEMA_VERSION,
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(json::SCHEMA_VERSION))
+    )]
+    schema: u32,
+
+    /// Group the JSON output by line, each with its index, the start of its baseline and its
+    /// shapes, e.g. to extrude lines to different depths
+    #[arg(long, conflicts_with_all = ["legacy_array", "nest"])]
+    group_by_line: bool,
+
+    /// Group the JSON output into regions, each an outer contour with the holes it contains,
+    /// ready to become faces with cutouts
+    #[arg(long, conflicts_with = "legacy_array")]
+    nest: bool,
+
+    /// Indent the JSON output so it is easier to read and diff
+    #[arg(long)]
+    pretty: bool,
+
+    /// Run the whole pipeline but print a summary of the geometry instead of writing it: the
+    /// number of shapes and primitives, the bounding box and the total path length. OUTPUT_FILE
+    /// may be left out. Combine with --strict to fail on missing glyphs or outlines, e.g. in CI
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Feed rate of cutting moves in G-code output, in millimetres per minute
+    #[arg(long, default_value_t = 300.0, value_parser = parse_positive)]
+    feed: f32,
+
+    /// Height the tool travels at between contours in G-code output, in millimetres
+    #[arg(long, default_value_t = 5.0, allow_negative_numbers = true)]
+    safe_z: f32,
+
+    /// Depth the tool cuts at in G-code output, in millimetres
+    #[arg(long, default_value_t = -0.5, allow_negative_numbers = true)]
+    cut_z: f32,
+
+    /// Write curves as DXF SPLINE entities instead of flattening them into polylines
+    #[arg(long)]
+    dxf_splines: bool,
+
+    /// Write the coordinates of DXF output with a decimal comma, e.g. "1,5", for importers that
+    /// parse numbers in a locale using one. Only DXF allows it: JSON, SVG, G-code and the other
+    /// formats require a decimal point by their specification and are unaffected
+    #[arg(long)]
+    decimal_comma: bool,
+
+    /// Name of the font family to use, e.g. "DejaVu Sans". Repeat it to list fallbacks, used in
+    /// order for characters the families before them have no glyph for
+    #[arg(long, conflicts_with = "font_file")]
+    font_family: Vec<String>,
+
+    /// Path to a .ttf, .otf or .ttc font file to use instead of the system fonts. Repeat it to
+    /// list fallbacks, used in order like those of --font-family
+    #[arg(long)]
+    font_file: Vec<String>,
+
+    /// Add every font in this directory and its subdirectories to the system fonts, e.g. the
+    /// fonts vendored by a project, to select them by name with --font-family. Can be repeated
+    #[arg(long, value_name = "DIR")]
+    font_db_dir: Vec<String>,
+
+    /// Index of the face to use when a font file is a collection (.ttc). Defaults to the first
+    /// face, listing all of them if there are several. Only one --font-file may be given with it
+    #[arg(long, requires = "font_file")]
+    font_index: Option<usize>,
+
+    /// Font weight, either numeric (100-900) or a keyword such as "regular" or "bold"
+    #[arg(long, value_parser = parse_weight)]
+    weight: Option<Weight>,
+
+    /// Coordinates on the axes of a variable font, e.g. "wght=650,wdth=75". Only the outlines are
+    /// varied, the spacing of the default instance is kept
+    #[arg(long, value_delimiter = ',', value_parser = parse_variation)]
+    variation: Vec<([u8; 4], f32)>,
+
+    /// Font style to request from the font
+    #[arg(long, value_enum)]
+    style: Option<FontStyle>,
+
+    /// Render the text as a superscript or subscript, shrunk and shifted off the baseline of
+    /// regular text. Generate each part separately with --origin baseline and --no-normalize so
+    /// they line up, e.g. for chemical formulas. The glyphs are always synthesized, dedicated
+    /// superscript glyphs of the font are not used
+    #[arg(long, value_enum, default_value_t = ScriptPosition::Normal)]
+    script: ScriptPosition,
+
+    /// Slant every glyph by this many degrees, shifting each point right by tan(angle) times its
+    /// height above the baseline. Works with any font, whether or not it has an italic face
+    #[arg(long, allow_hyphen_values = true)]
+    shear: Option<f32>,
+
+    /// Shear, in degrees, applied to glyphs whose face has no true italic for the requested style
+    #[arg(long, requires = "style")]
+    synthetic_italic: Option<f32>,
+
+    /// Shaping strategy. Basic is faster but skips complex cluster handling, such as ligatures and
+    /// the contextual forms needed by Arabic or Indic scripts
+    #[arg(long, value_enum, default_value_t = ShapingMode::Advanced)]
+    shaping: ShapingMode,
+
+    /// Base direction of each paragraph. Auto picks the direction of the first strongly
+    /// directional character, rtl lays out mixed text such as Hebrew with Latin numbers correctly
+    #[arg(long, value_enum, default_value_t = Direction::Auto)]
+    base_direction: Direction,
+}
+
+impl Args {
+    /// Checks that exactly one source of text was given. With --text-file only one positional
+    /// argument is expected, which clap assigns to `text`, so it is moved to `output_file`. Also
+    /// rejects a decimal comma for formats other than DXF, and options whose output JSON schema
+    /// version 2 cannot hold
+    fn validate(mut self) -> Self {
+        let error = |kind, message: &str| -> ! { Args::command().error(kind, message).exit() };
+
+        if self.batch.is_some() || self.list_fonts.is_some() || self.serve {
+            // clap already rejects the positional arguments and --text-file alongside these
+        } else if self.text_file.is_some() {
+            match (self.text.take(), self.output_file.take()) {
+                (Some(output_file), None) => self.output_file = Some(output_file),
+                (Some(_), Some(_)) => error(
+                    ErrorKind::ArgumentConflict,
+                    "TEXT cannot be used together with --text-file",
+                ),
+                (None, _) if self.dry_run => {}
+                (None, _) => error(
+                    ErrorKind::MissingRequiredArgument,
+                    "OUTPUT_FILE is required",
+                ),
+            }
+        } else if self.text.is_some() && self.dry_run {
+            // Nothing is written, so the output file may be left out
+        } else if self.output_file.is_none() {
+            error(
+                ErrorKind::MissingRequiredArgument,
+                "TEXT and OUTPUT_FILE are required, or --text-file and OUTPUT_FILE",
+            );
+        }
+        // Fallback files are rarely collections of the same layout, so one index would pick
+        // arbitrary faces from them
+        if self.font_index.is_some() && self.font_file.len() > 1 {
+            error(
+                ErrorKind::ArgumentConflict,
+                "--font-index cannot be used with more than one --font-file",
+            );
+        }
+        if self.decimal_comma && !matches!(self.format, OutputFormat::Dxf) {
+            error(
+                ErrorKind::ArgumentConflict,
+                "--decimal-comma is only supported with --format dxf",
+            );
+        }
+        if self.schema == 2 && matches!(self.format, OutputFormat::Json) {
+            let newer = [
+                ("--group-by-line", self.group_by_line),
+                ("--keep-close", self.keep_close),
+                ("--length", self.length),
+                ("--layout-boxes", self.layout_boxes),
+                ("--glyph-bbox", self.glyph_bbox),
+            ];
+            if let Some((flag, _)) = newer.iter().find(|(_, set)| *set) {
+                error(
+                    ErrorKind::ArgumentConflict,
+                    &format!("{flag} needs --schema 3, version 2 has no place for it"),
+                );
+            }
+        }
+
+        self
+    }
+
+    fn output_file(&self) -> &str {
+        match self.output_file.as_deref() {
+            Some(output_file) => output_file,
+            None if self.dry_run => "-",
+            None => panic!("Output file to be checked by Args::validate"),
+        }
+    }
+
+    /// Returns the text to render, reading it from --text-file or stdin if requested
+    fn read_text(&self) -> Result<String, Error> {
+        match self.text_file.as_deref() {
+            Some(path) => std::fs::read_to_string(path).map_err(|source| Error::Io {
+                context: format!("unable to read text file {path}"),
+                source,
+            }),
+            None => match self
+                .text
+                .as_deref()
+                .expect("Text to be checked by Args::validate")
+            {
+                "-" => {
+                    let mut text = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut text)
+                        .map_err(|source| Error::Io {
+                            context: "unable to read text from stdin".to_string(),
+                            source,
+                        })?;
+                    Ok(text)
+                }
+                text => Ok(text.to_string()),
+            },
+        }
+    }
+}
+
+/// One entry of a --batch manifest
+#[derive(Debug, Deserialize)]
+struct BatchEntry {
     text: String,
-    output_file: String,
+    output: String,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// A JSON object holding the shapes and their bounding box
+    Json,
+    /// One JSON shape per line, without the bounding box or metrics. The shapes are still all
+    /// rendered before the first line is written, this only avoids building the whole document
+    /// as one string. Coordinates are normalized like the other formats
+    Ndjson,
+    /// An SVG document with one path per shape, useful for previewing
+    Svg,
+    /// An ASCII DXF drawing with one closed polyline per contour
+    Dxf,
+    /// G-code tracing every contour, for engraving with a CNC router. Use --target-size so the
+    /// coordinates are in millimetres
+    Gcode,
+    /// A FreeCAD macro (.FCMacro) building a face from the contours of every glyph when run.
+    /// Coordinates are taken as millimetres
+    Fcmacro,
+    /// A STEP file (AP214) with the contours of every glyph as a wireframe of exact lines and
+    /// B-splines, to be turned into faces by the CAD program. Coordinates are taken as millimetres
+    #[cfg(feature = "step")]
+    Step,
+    /// An IGES file with the same wireframe as --format step, for CAD programs that import IGES
+    /// more reliably
+    #[cfg(feature = "step")]
+    Iges,
+}
 
-    // A FontSystem provides access to detected system fonts, create one per application
-    let mut font_system = FontSystem::new();
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ShapingMode {
+    /// Shape each character on its own, suitable for plain Latin text
+    Basic,
+    /// Full shaping with ligatures, kerning and complex script support
+    Advanced,
+}
 
-    // A SwashCache stores rasterized glyphs, create one per application
-    let mut swash_cache = SwashCache::new();
+impl From<ShapingMode> for Shaping {
+    fn from(mode: ShapingMode) -> Self {
+        match mode {
+            ShapingMode::Basic => Shaping::Basic,
+            ShapingMode::Advanced => Shaping::Advanced,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Direction {
+    Auto,
+    Ltr,
+    Rtl,
+}
 
-    // Text metrics indicate the font size and line height of a buffer
-    let metrics = Metrics::new(14.0, 20.0);
+impl From<Direction> for BaseDirection {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Auto => BaseDirection::Auto,
+            Direction::Ltr => BaseDirection::LeftToRight,
+            Direction::Rtl => BaseDirection::RightToLeft,
+        }
+    }
+}
 
-    // A Buffer provides shaping and layout for a UTF-8 string, create one per text widget
-    let mut buffer = Buffer::new(&mut font_system, metrics);
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OriginMode {
+    /// The minimum corner of the bounding box
+    Bbox,
+    /// The start of the first baseline
+    Baseline,
+    /// The center of the bounding box
+    Center,
+}
+
+impl From<OriginMode> for Origin {
+    fn from(mode: OriginMode) -> Self {
+        match mode {
+            OriginMode::Bbox => Origin::BoundingBox,
+            OriginMode::Baseline => Origin::Baseline,
+            OriginMode::Center => Origin::Center,
+        }
+    }
+}
 
-    // Borrow buffer together with the font system for more convenient method calls
-    let mut buffer = buffer.borrow_with(&mut font_system);
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Unit {
+    Mm,
+    In,
+    Pt,
+}
 
-    // Set a size for the text buffer, in pixels
-    buffer.set_size(Some(100000.0), Some(25.0));
+impl From<Unit> for Units {
+    fn from(unit: Unit) -> Self {
+        match unit {
+            Unit::Mm => Units::Millimetres,
+            Unit::In => Units::Inches,
+            Unit::Pt => Units::Points,
+        }
+    }
+}
 
-    // Attributes indicate what font to choose
-    let attrs = Attrs::new();
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Alignment {
+    Left,
+    Center,
+    Right,
+    /// Stretch the spaces of wrapped lines to fill the width, the last line of each paragraph
+    /// stays left aligned
+    Justify,
+}
 
-    // Add some text!
-    buffer.set_text(&args.text, attrs, Shaping::Advanced);
+impl From<Alignment> for Align {
+    fn from(alignment: Alignment) -> Self {
+        match alignment {
+            Alignment::Left => Align::Left,
+            Alignment::Center => Align::Center,
+            Alignment::Right => Align::Right,
+            Alignment::Justify => Align::Justified,
+        }
+    }
+}
 
-    // Perform shaping as desired
-    buffer.shape_until_scroll(true);
-    let mut symbols: Vec<(i32, i32, CacheKey)> = vec![];
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum UnicodeForm {
+    Nfc,
+    Nfd,
+    /// Shape the text exactly as given
+    None,
+}
 
-    for run in buffer.layout_runs() {
-        for glyph in run.glyphs.iter() {
-            let physical_glyph = glyph.physical((0., 0.), 1.0);
+impl From<UnicodeForm> for Option<NormalizationForm> {
+    fn from(form: UnicodeForm) -> Self {
+        match form {
+            UnicodeForm::Nfc => Some(NormalizationForm::Nfc),
+            UnicodeForm::Nfd => Some(NormalizationForm::Nfd),
+            UnicodeForm::None => None,
+        }
+    }
+}
 
-            let x = physical_glyph.x;
-            let y = run.line_y as i32 + physical_glyph.y;
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ScriptPosition {
+    Normal,
+    Super,
+    Sub,
+}
 
-            symbols.push((x, y, physical_glyph.cache_key));
+impl From<ScriptPosition> for Script {
+    fn from(position: ScriptPosition) -> Self {
+        match position {
+            ScriptPosition::Normal => Script::Normal,
+            ScriptPosition::Super => Script::Superscript,
+            ScriptPosition::Sub => Script::Subscript,
         }
     }
+}
 
-    let mut shapes = vec![];
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FillMode {
+    Evenodd,
+    Nonzero,
+}
 
-    for (x, y, key) in symbols {
-        let commands: Vec<_> = swash_cache
-            .get_outline_commands(&mut font_system, key)
-            .expect(format!("Expected a list of commands for character").as_ref())
-            .iter()
-            .map(|v| v.transform(&Transform::translation(x as f32, y as f32)))
-            .collect();
+impl From<FillMode> for FillRule {
+    fn from(mode: FillMode) -> Self {
+        match mode {
+            FillMode::Evenodd => FillRule::EvenOdd,
+            FillMode::Nonzero => FillRule::NonZero,
+        }
+    }
+}
 
-        let mut last_point: Option<Point> = None;
-        let mut first_point: Option<Point> = None;
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Mirror {
+    X,
+    Y,
+    Xy,
+}
 
-        let mut primitives = vec![];
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
 
-        if commands.len() == 0 {
-            continue;
+impl From<FontStyle> for Style {
+    fn from(style: FontStyle) -> Self {
+        match style {
+            FontStyle::Normal => Style::Normal,
+            FontStyle::Italic => Style::Italic,
+            FontStyle::Oblique => Style::Oblique,
         }
+    }
+}
 
-        for command in commands {
-            match command {
-                Command::MoveTo(end_point) => {
-                    first_point.get_or_insert(Point(end_point.x, end_point.y));
-                    last_point = Some(Point(end_point.x, end_point.y))
-                }
-                Command::QuadTo(ctrl_point0, end_point) => {
-                    let from_point = last_point.expect("Cannot QuadTo without a previous point");
-                    let end_point = Point(end_point.x, end_point.y);
-                    primitives.push(Primitive::Quadratic(
-                        from_point,
-                        Point(ctrl_point0.x, ctrl_point0.y),
-                        end_point.clone(),
-                    ));
-                    last_point = Some(end_point);
-                }
-                Command::CurveTo(ctrl_point0, ctrl_point1, end_point) => {
-                    let from_point = last_point.expect("Cannot CurveTo without a previous point");
-                    let end_point = Point(end_point.x, end_point.y);
-                    primitives.push(Primitive::Bezier(
-                        from_point,
-                        Point(ctrl_point0.x, ctrl_point0.y),
-                        Point(ctrl_point1.x, ctrl_point1.y),
-                        end_point.clone(),
-                    ));
-                    last_point = Some(end_point);
-                }
-                Command::LineTo(end_point) => {
-                    let from_point = last_point.expect("Cannot LineTo without a previous point");
-                    let end_point = Point(end_point.x, end_point.y);
-                    primitives.push(Primitive::Line(from_point, end_point.clone()));
-                    last_point = Some(end_point);
-                }
-                Command::Close => {
-                    let from_point = last_point.expect("Cannot LineTo without a previous point");
-                    let end_point = first_point
-                        .take()
-                        .expect("Cannot \"Close\" without a starting point");
-                    primitives.push(Primitive::Line(from_point, end_point.clone()));
-                    last_point = Some(end_point);
-                }
+fn parse_positive(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("expected a positive number, got {value}"))
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("expected a number of at least 0, got {value}"))
+    }
+}
+
+fn parse_range(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected START..END, got \"{s}\""))?;
+    let index = |index: &str, default| match index {
+        "" => Ok(default),
+        index => index.parse().map_err(|e| format!("{e}")),
+    };
+    let range = index(start, 0)?..index(end, usize::MAX)?;
+    if range.start < range.end {
+        Ok(range)
+    } else {
+        Err(format!("expected START below END, got \"{s}\""))
+    }
+}
+
+fn parse_padding(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..0.5).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("expected a fraction from 0 up to 0.5, got {value}"))
+    }
+}
+
+fn parse_variation(s: &str) -> Result<([u8; 4], f32), String> {
+    let (tag, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected AXIS=VALUE, got \"{s}\""))?;
+    let tag: [u8; 4] = tag
+        .as_bytes()
+        .try_into()
+        .ok()
+        .filter(|tag: &[u8; 4]| tag.iter().all(u8::is_ascii_graphic))
+        .ok_or_else(|| format!("expected a four character axis tag, got \"{tag}\""))?;
+    let value: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    Ok((tag, value))
+}
+
+fn parse_weight(s: &str) -> Result<Weight, String> {
+    let weight = match s.to_lowercase().as_str() {
+        "thin" => Weight::THIN,
+        "extra-light" | "extralight" => Weight::EXTRA_LIGHT,
+        "light" => Weight::LIGHT,
+        "normal" | "regular" => Weight::NORMAL,
+        "medium" => Weight::MEDIUM,
+        "semi-bold" | "semibold" => Weight::SEMIBOLD,
+        "bold" => Weight::BOLD,
+        "extra-bold" | "extrabold" => Weight::EXTRA_BOLD,
+        "black" => Weight::BLACK,
+        other => {
+            let value: u16 = other
+                .parse()
+                .map_err(|_| format!("unknown weight \"{other}\""))?;
+            if !(100..=900).contains(&value) {
+                return Err(format!(
+                    "expected a weight between 100 and 900, got {value}"
+                ));
             }
+            Weight(value)
         }
+    };
+    Ok(weight)
+}
 
-        let s = Shape { primitives };
+/// Prints every face whose family contains `filter`, ignoring case, sorted by family
+fn list_fonts(font_system: &FontSystem, filter: &str) {
+    let filter = filter.to_lowercase();
+    let mut faces: Vec<_> = font_system
+        .db()
+        .faces()
+        .filter_map(|face| {
+            let (family, _) = face.families.first()?;
+            family
+                .to_lowercase()
+                .contains(&filter)
+                .then_some((family, face))
+        })
+        .collect();
+    faces.sort_by(|(a, a_face), (b, b_face)| {
+        a.cmp(b)
+            .then(a_face.weight.cmp(&b_face.weight))
+            .then(a_face.post_script_name.cmp(&b_face.post_script_name))
+    });
 
-        shapes.push(s);
+    for (family, face) in faces {
+        let source = match &face.source {
+            Source::File(path) | Source::SharedFile(path, _) => path.display().to_string(),
+            Source::Binary(_) => "(loaded from memory)".to_string(),
+        };
+        println!(
+            "{family}\t{:?}\t{}\t{}\t{source}",
+            face.style, face.weight.0, face.post_script_name
+        );
     }
+}
 
-    let (min_point, max_point) = shapes.first().expect("Geometry has no shapes").get_bb();
-    let points = shapes
-        .iter()
-        .map(|s| s.get_bb())
-        .fold((min_point, max_point), |(min_p, max_p), (p0, p1)| {
-            (min_p.min(&p0), max_p.max(&p1))
-        });
-    shapes = shapes
-        .into_iter()
-        .map(|shape| shape.remap_shape(&points.0, &points.1))
+/// Prints a warning listing every face of the collection at `path`, of which the first is used
+fn warn_about_collection(path: &str, faces: &[FaceInfo]) {
+    eprintln!(
+        "warning: {path} contains {} faces, using face 0. Select another one with --font-index:",
+        faces.len()
+    );
+    for (i, face) in faces.iter().enumerate() {
+        let family = face.families.first().map_or("", |(name, _)| name.as_str());
+        eprintln!(
+            "  {i}: {family} ({}, weight {}, {:?})",
+            face.post_script_name, face.weight.0, face.style
+        );
+    }
+}
+
+/// Prints a warning listing the closest known families if `family` is not in the font database
+fn warn_if_family_missing(font_system: &FontSystem, family: &str) {
+    let wanted = family.to_lowercase();
+    let mut families: Vec<&str> = font_system
+        .db()
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(name, _)| name.as_str()))
         .collect();
 
-    let out = serde_json::to_string(&shapes).expect("to be able to serialize shape");
-    let mut file =
-        std::fs::File::create(args.output_file).expect("To be able to create output file");
-    file.write(&out.into_bytes())
-        .expect("To be able to write into file");
+    if families.iter().any(|name| name.to_lowercase() == wanted) {
+        return;
+    }
+
+    families.sort_by_key(|name| strsim::levenshtein(&name.to_lowercase(), &wanted));
+    families.dedup();
+
+    eprintln!("warning: font family \"{family}\" was not found, a fallback font will be used");
+    if !families.is_empty() {
+        eprintln!(
+            "warning: closest matches: {}",
+            families
+                .iter()
+                .take(5)
+                .copied()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
 }
 
-use serde::Serialize;
-use std::io::Write;
+fn main() -> ExitCode {
+    let args = Args::parse().validate();
 
-#[derive(Clone, Debug, Serialize)]
-struct Point(f32, f32);
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-impl Point {
-    fn map_scale(self, min_point: &Point, max_point: &Point) -> Self {
-        let x = (self.0 - min_point.0) / (max_point.1 - min_point.1);
-        let y = (self.1 - min_point.1) / (max_point.1 - min_point.1);
-        Point(
-            ((x * 1000.0 as f32) as i32) as f32 / 1000.0,
-            ((y * 1000.0 as f32) as i32) as f32 / 1000.0,
-        )
+fn run(args: Args) -> Result<(), Error> {
+    // A FontSystem provides access to detected system fonts, create one per application
+    let mut font_system = FontSystem::new();
+
+    for dir in args.font_db_dir.iter() {
+        if fonts::load_font_dir(&mut font_system, dir)? == 0 {
+            eprintln!("warning: no fonts found in {dir}");
+        }
     }
 
-    fn min(&self, other: &Point) -> Point {
-        Point(f32::min(self.0, other.0), f32::min(self.1, other.1))
+    for family in args.font_family.iter() {
+        warn_if_family_missing(&font_system, family);
     }
 
-    fn max(&self, other: &Point) -> Point {
-        Point(f32::max(self.0, other.0), f32::max(self.1, other.1))
+    let mut font_faces = vec![];
+    for path in args.font_file.iter() {
+        let font_file = fonts::load_font_file(&mut font_system, path, args.font_index)?;
+        if args.font_index.is_none() && font_file.faces.len() > 1 {
+            warn_about_collection(path, &font_file.faces);
+        }
+        font_faces.push(font_file.face);
+    }
+
+    if let Some(filter) = args.list_fonts.as_deref() {
+        list_fonts(&font_system, filter);
+        return Ok(());
+    }
+
+    let mut opts = RenderOptions {
+        font_size: args.font_size,
+        render_size: args.render_size,
+        scale_factor: args
+            .scale_factor
+            .or(args.dpi.map(|dpi| dpi / 96.0))
+            .unwrap_or(1.0),
+        line_height: args.line_height,
+        line_gap: args.line_gap,
+        wrap_width: args.wrap_width,
+        align: args.align.map(Align::from),
+        normalize_unicode: args.normalize_unicode.into(),
+        range: args.range.clone(),
+        vertical: args.vertical,
+        tab_width: args.tab_width,
+        font_family: args.font_family.first().cloned(),
+        fallback_families: args.font_family.iter().skip(1).cloned().collect(),
+        weight: args.weight,
+        style: args.style.map(Style::from),
+        stretch: None,
+        variations: args.variation.clone(),
+        script: args.script.into(),
+        synthetic_italic: args.synthetic_italic,
+        shear: args.shear,
+        shaping: args.shaping.into(),
+        base_direction: args.base_direction.into(),
+        letter_spacing: args.letter_spacing,
+        kerning: !args.no_kerning,
+        flatten_color: args.flatten_color,
+        strict: args.strict,
+        layout_boxes: args.layout_boxes,
+        glyph_boxes: args.glyph_bbox,
+        lengths: args.length,
+        stroke: args.stroke,
+        emit_empty: args.emit_empty,
+        rotate: args.rotate,
+        mirror_x: matches!(args.mirror, Some(Mirror::X | Mirror::Xy)),
+        mirror_y: matches!(args.mirror, Some(Mirror::Y | Mirror::Xy)),
+        flip_y: args.flip_y,
+        normalize: !args.no_normalize,
+        per_glyph_normalize: args.per_glyph_normalize,
+        bbox_pad: args.bbox_pad,
+        target_size: args
+            .target_size
+            .as_deref()
+            .map(|size| match *size {
+                [longest] => TargetSize::Longest(longest),
+                [width, height] => TargetSize::Exact(width, height),
+                _ => unreachable!("clap accepts one or two target sizes"),
+            })
+            .or(args.fit_width.map(TargetSize::Width)),
+        units: args.units.map(Units::from),
+        scale: args.scale,
+        origin: args
+            .origin
+            .map(Origin::from)
+            .or(args.center_origin.then_some(Origin::Center)),
+        offset: args.offset.as_deref().map(|offset| (offset[0], offset[1])),
+        cubic_only: args.cubic_only,
+        keep_close: args.keep_close,
+        // G-code only knows straight moves, flattening before rounding keeps its precision
+        flatten: match args.format {
+            OutputFormat::Gcode => Some(args.flatten.unwrap_or(gcode::DEFAULT_TOLERANCE)),
+            _ => args.flatten,
+        },
+        union: args.union,
+        invert: args.invert,
+        max_segments: args.max_segments,
+        simplify: args.simplify,
+        min_contour_area: args.min_contour_area,
+        straighten: args.straighten,
+        fill_rule: args.fill_rule.into(),
+        reverse_contours: args.reverse_contours,
+        precision: args.precision,
+        stable_order: args.stable_order,
+        reuse_outlines: true,
+    };
+    let families = font_faces
+        .iter()
+        .map(|face| {
+            face.families
+                .first()
+                .map(|(family, _)| family.clone())
+                .ok_or_else(|| Error::FontNotFound(face.post_script_name.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(face) = font_faces.first() {
+        opts.font_family = families.first().cloned();
+        opts.fallback_families = families[1..].to_vec();
+        opts.weight = Some(args.weight.unwrap_or(face.weight));
+        opts.style = Some(opts.style.unwrap_or(face.style));
+        opts.stretch = Some(face.stretch);
+    }
+
+    // A SwashCache stores rasterized glyphs, create one per application
+    let mut swash_cache = SwashCache::new();
+
+    if args.serve {
+        return serve(&args, &opts, &mut font_system, &mut swash_cache);
+    }
+    match args.batch.as_deref() {
+        Some(manifest) => run_batch(&args, manifest, &opts, &mut font_system, &mut swash_cache),
+        None => {
+            let text = args.read_text()?;
+            render(
+                &args,
+                &opts,
+                &mut font_system,
+                &mut swash_cache,
+                &text,
+                args.output_file(),
+            )
+        }
     }
 }
 
-#[derive(Debug, Serialize)]
-enum Primitive {
-    Quadratic(Point, Point, Point),
-    Bezier(Point, Point, Point, Point),
-    Line(Point, Point),
+/// Prints what rendering produced, for --dry-run
+fn print_summary(shapes: &[Shape], min_point: &Point, max_point: &Point, opts: &RenderOptions) {
+    let primitives: usize = shapes.iter().map(|shape| shape.primitives.len()).sum();
+    let length: f32 = shapes.iter().map(Shape::length).sum();
+    let unit = match opts.units {
+        Some(Units::Millimetres) => " mm",
+        Some(Units::Inches) => " in",
+        Some(Units::Points) => " pt",
+        None => "",
+    };
+    println!("shapes: {}", shapes.len());
+    println!("primitives: {primitives}");
+    println!(
+        "bounding box: {}, {} to {}, {}{unit}",
+        min_point.0, min_point.1, max_point.0, max_point.1
+    );
+    println!("path length: {length:.*}{unit}", opts.precision as usize);
 }
 
-#[derive(Debug, Serialize)]
-struct Shape {
-    primitives: Vec<Primitive>,
+/// Renders every entry of the manifest at `path`, failing only once all entries were attempted
+fn run_batch(
+    args: &Args,
+    path: &str,
+    opts: &RenderOptions,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) -> Result<(), Error> {
+    let manifest = std::fs::read_to_string(path).map_err(|source| Error::Io {
+        context: format!("unable to read batch manifest {path}"),
+        source,
+    })?;
+    let entries: Vec<BatchEntry> =
+        serde_json::from_str(&manifest).map_err(|source| Error::InvalidManifest {
+            path: path.to_string(),
+            source,
+        })?;
+
+    let mut failed = 0;
+    for entry in entries.iter() {
+        match render(
+            args,
+            opts,
+            font_system,
+            swash_cache,
+            &entry.text,
+            &entry.output,
+        ) {
+            Ok(()) => eprintln!("{}: ok", entry.output),
+            Err(e) => {
+                eprintln!("{}: error: {e}", entry.output);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::BatchFailed {
+            failed,
+            total: entries.len(),
+        });
+    }
+    Ok(())
 }
 
-impl Shape {
-    fn get_bb(&self) -> (Point, Point) {
-        let mut points = vec![];
-        for p in self.primitives.iter() {
-            match p {
-                Primitive::Quadratic(p1, p2, p3) => {
-                    points.push(p1);
-                    points.push(p2);
-                    points.push(p3);
-                }
-                Primitive::Bezier(p1, p2, p3, p4) => {
-                    points.push(p1);
-                    points.push(p2);
-                    points.push(p3);
-                    points.push(p4);
-                }
-                Primitive::Line(p1, p2) => {
-                    points.push(p1);
-                    points.push(p2);
+/// Answers the requests read from stdin until it is closed, see --serve
+fn serve(
+    args: &Args,
+    opts: &RenderOptions,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|source| Error::Io {
+            context: "unable to read requests from stdin".to_string(),
+            source,
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match line.rsplit_once('\t') {
+            // Responses share stdout, so the output cannot be written there
+            Some((_, "-")) => "error\tOUTPUT_FILE cannot be \"-\" in server mode".to_string(),
+            Some((text, output_file)) => {
+                match render(
+                    args,
+                    opts,
+                    font_system,
+                    swash_cache,
+                    &unescape(text),
+                    output_file,
+                ) {
+                    Ok(()) => format!("ok\t{output_file}"),
+                    Err(e) => format!("error\t{e}"),
                 }
             }
+            None => "error\texpected TEXT<TAB>OUTPUT_FILE".to_string(),
+        };
+        writeln!(stdout, "{response}")
+            .and_then(|()| stdout.flush())
+            .map_err(|source| Error::Io {
+                context: "unable to write to stdout".to_string(),
+                source,
+            })?;
+    }
+    Ok(())
+}
+
+/// Replaces the escapes of a --serve request by the characters they stand for. Unknown escapes
+/// are kept as they are
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
         }
+    }
+    unescaped
+}
 
-        let min_x = points
-            .iter()
-            .map(|point| point.0)
-            .min_by(|a, b| a.partial_cmp(b).expect("Unable to order floats"))
-            .expect("No items found");
-
-        let min_y = points
-            .iter()
-            .map(|point| point.1)
-            .min_by(|a, b| a.partial_cmp(b).expect("Unable to order floats"))
-            .expect("No items found");
-
-        let max_x = points
-            .iter()
-            .map(|point| point.0)
-            .max_by(|a, b| a.partial_cmp(b).expect("Unable to order floats"))
-            .expect("No items found");
-
-        let max_y = points
-            .iter()
-            .map(|point| point.1)
-            .max_by(|a, b| a.partial_cmp(b).expect("Unable to order floats"))
-            .expect("No items found");
-
-        (Point(min_x, min_y), Point(max_x, max_y))
-    }
-
-    fn remap_shape(self, min_point: &Point, max_point: &Point) -> Self {
-        let primitives = self
-            .primitives
-            .into_iter()
-            .map(|primitive| match primitive {
-                Primitive::Quadratic(p1, p2, p3) => Primitive::Quadratic(
-                    p1.map_scale(&min_point, &max_point),
-                    p2.map_scale(&min_point, &max_point),
-                    p3.map_scale(&min_point, &max_point),
-                ),
-                Primitive::Bezier(p1, p2, p3, p4) => Primitive::Bezier(
-                    p1.map_scale(&min_point, &max_point),
-                    p2.map_scale(&min_point, &max_point),
-                    p3.map_scale(&min_point, &max_point),
-                    p4.map_scale(&min_point, &max_point),
-                ),
-                Primitive::Line(p1, p2) => Primitive::Line(
-                    p1.map_scale(&min_point, &max_point),
-                    p2.map_scale(&min_point, &max_point),
-                ),
-            })
-            .collect();
+/// Fails early if `output_file` cannot be written, before any time is spent on shaping. The file
+/// itself is left untouched so a failed run does not clobber it
+fn check_writable(output_file: &str) -> Result<(), Error> {
+    if output_file == "-" {
+        return Ok(());
+    }
+    let not_writable = |reason: String| Error::OutputNotWritable {
+        path: output_file.to_string(),
+        reason,
+    };
 
-        Self { primitives }
+    let path = Path::new(output_file);
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !parent.is_dir() {
+        return Err(not_writable(format!(
+            "directory {} does not exist",
+            parent.display()
+        )));
     }
+
+    if path.exists() {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| not_writable(e.to_string()))?;
+    } else if parent
+        .metadata()
+        .is_ok_and(|metadata| metadata.permissions().readonly())
+    {
+        return Err(not_writable(format!(
+            "directory {} is read-only",
+            parent.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Turns `text` into geometry and writes it to `output_file` in the requested format
+fn render(
+    args: &Args,
+    opts: &RenderOptions,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+    text: &str,
+    output_file: &str,
+) -> Result<(), Error> {
+    if !args.dry_run {
+        check_writable(output_file)?;
+    }
+    let fonts::Rendering {
+        shapes,
+        metrics,
+        baselines,
+        warnings,
+        target_scale,
+        effective_font_size,
+        simplified_vertices,
+        dropped_contours,
+    } = fonts::render_text(text, opts, font_system, swash_cache)?;
+    for warning in warnings.iter() {
+        eprintln!("warning: {warning}");
+    }
+    if let Some((scale_x, scale_y)) = target_scale {
+        match opts.target_size {
+            Some(TargetSize::Exact(..)) => eprintln!("scale factors: {scale_x}, {scale_y}"),
+            Some(TargetSize::Width(_)) => {
+                eprintln!("scale factor: {scale_x}, effective font size: {effective_font_size}")
+            }
+            Some(TargetSize::Longest(_)) | None => eprintln!("scale factor: {scale_x}"),
+        }
+    }
+    if let Some((before, after)) = simplified_vertices {
+        eprintln!("simplified from {before} to {after} vertices");
+    }
+    if let Some(min_area) = opts.min_contour_area {
+        eprintln!("dropped {dropped_contours} contours below an area of {min_area}");
+    }
+
+    // Every format needs at least one glyph outline to produce a meaningful drawing, so empty and
+    // whitespace-only input is rejected instead of writing an empty file
+    let (min_point, max_point) = bounding_box(&shapes).ok_or(Error::EmptyGeometry)?;
+    if args.dry_run {
+        print_summary(&shapes, &min_point, &max_point, opts);
+        return Ok(());
+    }
+    let out = match args.format {
+        OutputFormat::Ndjson => None,
+        OutputFormat::Json if args.legacy_array || args.schema == 1 => {
+            Some(json::to_legacy_json(&shapes, args.pretty)?)
+        }
+        OutputFormat::Json if args.group_by_line => Some(json::to_line_json(
+            &json::group_by_line(&shapes, &baselines),
+            &min_point,
+            &max_point,
+            opts.units,
+            &metrics,
+            args.pretty,
+        )?),
+        OutputFormat::Json if args.nest => Some(json::to_nested_json(
+            &nest::nest(&shapes),
+            &min_point,
+            &max_point,
+            opts.units,
+            &metrics,
+            args.schema,
+            args.pretty,
+        )?),
+        OutputFormat::Json => Some(json::to_json(
+            &shapes,
+            &min_point,
+            &max_point,
+            opts.units,
+            &metrics,
+            args.schema,
+            args.pretty,
+        )?),
+        OutputFormat::Svg => Some(svg::to_svg(&shapes, &min_point, &max_point)),
+        OutputFormat::Fcmacro => Some(fcmacro::to_fcmacro(&shapes)),
+        #[cfg(feature = "step")]
+        OutputFormat::Step => Some(fonts::step::to_step(&shapes)),
+        #[cfg(feature = "step")]
+        OutputFormat::Iges => Some(fonts::iges::to_iges(&shapes)),
+        OutputFormat::Dxf => Some(dxf::to_dxf(
+            &shapes,
+            args.dxf_splines,
+            opts.units,
+            args.decimal_comma,
+        )),
+        OutputFormat::Gcode => Some(gcode::to_gcode(
+            &shapes,
+            args.flatten.unwrap_or(gcode::DEFAULT_TOLERANCE),
+            args.feed,
+            args.safe_z,
+            args.cut_z,
+        )),
+    };
+    let file: Box<dyn Write> = if output_file == "-" {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(
+            std::fs::File::create(output_file).map_err(|source| Error::Io {
+                context: format!("unable to create output file {output_file}"),
+                source,
+            })?,
+        )
+    };
+    let mut file = BufWriter::new(file);
+    match out {
+        Some(out) => file.write_all(out.as_bytes()),
+        // NDJSON is written shape by shape instead of being collected into one string first
+        None => json::write_ndjson(&shapes, &mut file),
+    }
+    .and_then(|()| file.flush())
+    .map_err(|source| Error::Io {
+        context: format!("unable to write to {output_file}"),
+        source,
+    })?;
+
+    Ok(())
 }
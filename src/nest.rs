@@ -0,0 +1,125 @@
+use crate::{Point, Shape};
+use serde::{Deserialize, Serialize};
+
+/// Number of line segments each curve is split into for the containment tests
+const CURVE_SEGMENTS: usize = 16;
+
+/// An outer contour together with the holes cut out of it, the input FreeCAD needs to build a
+/// face with cutouts
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub outer: Shape,
+    pub holes: Vec<Shape>,
+}
+
+/// Groups the contours of every glyph into outer contours and the holes they contain.
+///
+/// A contour nested inside an odd number of the other contours of its glyph is a hole, and
+/// belongs to the smallest contour around it. The winding is not used, so mirrored geometry nests
+/// the same way. Islands inside holes, such as the middle of a "®", become regions of their own.
+/// Shapes without primitives are dropped.
+pub fn nest(shapes: &[Shape]) -> Vec<Region> {
+    let shapes: Vec<&Shape> = shapes.iter().filter(|s| !s.primitives.is_empty()).collect();
+    let (parents, areas) = enclosing(&shapes);
+
+    let mut regions: Vec<Option<Region>> = shapes
+        .iter()
+        .zip(parents.iter())
+        .map(|(shape, parents)| {
+            (parents.len() % 2 == 0).then(|| Region {
+                outer: (*shape).clone(),
+                holes: vec![],
+            })
+        })
+        .collect();
+    for (i, parents) in parents.iter().enumerate() {
+        if parents.len() % 2 == 0 {
+            continue;
+        }
+        let outer = parents
+            .iter()
+            .filter(|&&j| regions[j].is_some())
+            .min_by(|&&a, &&b| areas[a].total_cmp(&areas[b]));
+        if let Some(region) = outer.and_then(|&j| regions[j].as_mut()) {
+            region.holes.push(shapes[i].clone());
+        }
+    }
+    regions.into_iter().flatten().collect()
+}
+
+/// Reverses contours so outer contours run counter-clockwise and holes clockwise, as consumers
+/// filling with the nonzero rule expect. Nesting depth is determined as in [`nest`], and the
+/// winding each contour has in the font is ignored
+pub fn orient_for_nonzero(shapes: Vec<Shape>) -> Vec<Shape> {
+    let parents = enclosing(&shapes.iter().collect::<Vec<_>>()).0;
+    shapes
+        .into_iter()
+        .zip(parents)
+        .map(|(shape, parents)| {
+            let hole = parents.len() % 2 == 1;
+            if !shape.primitives.is_empty() && hole == (shape.signed_area() > 0.0) {
+                shape.reverse()
+            } else {
+                shape
+            }
+        })
+        .collect()
+}
+
+/// For every shape, the indices of the contours of the same glyph enclosing it, together with
+/// the unsigned area of every shape. Shapes without primitives neither enclose nor are enclosed
+fn enclosing(shapes: &[&Shape]) -> (Vec<Vec<usize>>, Vec<f32>) {
+    let polygons: Vec<Vec<Point>> = shapes.iter().map(|shape| polygon(shape)).collect();
+    let areas: Vec<f32> = polygons.iter().map(|points| area(points).abs()).collect();
+    let parents = (0..shapes.len())
+        .map(|i| {
+            (0..shapes.len())
+                .filter(|&j| {
+                    j != i
+                        && shapes[j].index == shapes[i].index
+                        && areas[j] > areas[i]
+                        && polygons[i]
+                            .first()
+                            .is_some_and(|point| contains(&polygons[j], point))
+                })
+                .collect()
+        })
+        .collect();
+    (parents, areas)
+}
+
+/// Flattens the shape into the corners of a polygon
+fn polygon(shape: &Shape) -> Vec<Point> {
+    shape
+        .primitives
+        .iter()
+        .flat_map(|primitive| {
+            let mut points = primitive.sample(CURVE_SEGMENTS);
+            points.pop();
+            points
+        })
+        .collect()
+}
+
+/// Signed area of the polygon, using the shoelace formula
+fn area(points: &[Point]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Whether `point` lies inside the polygon, by counting the edges a ray to the right crosses
+fn contains(polygon: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
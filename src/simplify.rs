@@ -0,0 +1,91 @@
+use crate::flatten::distance_to_segment;
+use crate::{Point, Primitive, Shape};
+
+/// Drops the vertices of straight line chains that deviate at most `epsilon` from the simplified
+/// chain, using the Ramer-Douglas-Peucker algorithm.
+///
+/// Curves are kept as they are, as are the end points of every chain, so closed contours stay
+/// closed. Run [`crate::flatten::flatten_shape`] first to simplify curves as well.
+pub fn simplify_shape(shape: Shape, epsilon: f32) -> Shape {
+    shape.map_primitives(|primitives| {
+        let mut simplified = vec![];
+        let mut chain: Vec<Point> = vec![];
+        for primitive in primitives {
+            match primitive {
+                Primitive::Line(start, end) => {
+                    if chain.last() != Some(&start) {
+                        push_chain(&chain, epsilon, &mut simplified);
+                        chain = vec![start];
+                    }
+                    chain.push(end);
+                }
+                curve => {
+                    push_chain(&chain, epsilon, &mut simplified);
+                    chain.clear();
+                    simplified.push(curve);
+                }
+            }
+        }
+        push_chain(&chain, epsilon, &mut simplified);
+        simplified
+    })
+}
+
+/// Number of vertices of the shape, each primitive counting its control points and end point
+pub fn vertex_count(shape: &Shape) -> usize {
+    shape
+        .primitives
+        .iter()
+        .map(|primitive| match primitive {
+            Primitive::Line(..) | Primitive::Close(..) => 1,
+            Primitive::Quadratic(..) => 2,
+            Primitive::Bezier(..) => 3,
+        })
+        .sum()
+}
+
+fn push_chain(chain: &[Point], epsilon: f32, out: &mut Vec<Primitive>) {
+    if chain.len() < 2 {
+        return;
+    }
+    let mut keep = vec![false; chain.len()];
+    keep[0] = true;
+    keep[chain.len() - 1] = true;
+    mark(chain, epsilon, &mut keep);
+
+    let kept: Vec<&Point> = chain
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(point))
+        .collect();
+    out.extend(
+        kept.windows(2)
+            .map(|pair| Primitive::Line(pair[0].clone(), pair[1].clone())),
+    );
+}
+
+/// Marks the point farthest from the chord between the first and last point as kept if it lies
+/// further than `epsilon` from it, then recurses into both halves
+fn mark(points: &[Point], epsilon: f32, keep: &mut [bool]) {
+    if points.len() < 3 {
+        return;
+    }
+    let (first, last) = (&points[0], &points[points.len() - 1]);
+    let (index, distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, point)| (i + 1, distance_to_segment(point, first, last)))
+        .fold((0, 0.0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+
+    if distance > epsilon {
+        keep[index] = true;
+        mark(&points[..=index], epsilon, &mut keep[..=index]);
+        mark(&points[index..], epsilon, &mut keep[index..]);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{Point, Primitive, Shape};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Renders the shapes as a STEP file (ISO 10303-21, AP214) holding the outline of every glyph as
+/// a wireframe. This is a minimal exporter written without a CAD kernel: it emits only the
+/// entities a wireframe needs and does not validate them against the schema.
+///
+/// Every contour becomes a composite curve of B-spline segments, of degree 1 for lines, 2 for
+/// quadratic and 3 for cubic curves, with the control points of the primitive as poles, so the
+/// outline is reproduced exactly. The contours of a glyph are grouped into a curve set named
+/// after its text. Only the 2D profile is written, in the XY plane: no faces, solids or
+/// extrusions, which the importing CAD program builds from the wires. Coordinates are taken as
+/// millimetres.
+pub fn to_step(shapes: &[Shape]) -> String {
+    let mut glyphs: BTreeMap<usize, Vec<&Shape>> = BTreeMap::new();
+    for shape in shapes.iter().filter(|shape| !shape.primitives.is_empty()) {
+        glyphs.entry(shape.index).or_default().push(shape);
+    }
+
+    let mut step = Step::default();
+    let context = step.context();
+    let mut items = vec![];
+    for shapes in glyphs.values() {
+        let mut curves = vec![];
+        for contour in shapes.iter().flat_map(|shape| shape.contours()) {
+            let segments: Vec<String> = contour
+                .iter()
+                // Zero length edges are rejected by CAD kernels, the composite curve is closed
+                // without them
+                .filter(|primitive| !matches!(primitive, Primitive::Close(p0, p1) if p0 == p1))
+                .map(|primitive| {
+                    let curve = step.curve(primitive);
+                    step.entity(format!("COMPOSITE_CURVE_SEGMENT(.CONTINUOUS.,.T.,{curve})"))
+                })
+                .collect();
+            if !segments.is_empty() {
+                curves
+                    .push(step.entity(format!("COMPOSITE_CURVE('',({}),.U.)", segments.join(","))));
+            }
+        }
+        items.push(step.entity(format!(
+            "GEOMETRIC_CURVE_SET({},({}))",
+            step_string(&shapes[0].character),
+            curves.join(",")
+        )));
+    }
+    let origin = step.entity("CARTESIAN_POINT('',(0.,0.,0.))".to_string());
+    let placement = step.entity(format!("AXIS2_PLACEMENT_3D('',{origin},$,$)"));
+    items.push(placement);
+    let representation = step.entity(format!(
+        "GEOMETRICALLY_BOUNDED_WIREFRAME_SHAPE_REPRESENTATION('Text',({}),{context})",
+        items.join(",")
+    ));
+    let product = step.product();
+    step.entity(format!(
+        "SHAPE_DEFINITION_REPRESENTATION({product},{representation})"
+    ));
+
+    let mut out = String::from("ISO-10303-21;\nHEADER;\n");
+    out.push_str("FILE_DESCRIPTION(('Text outlines'),'2;1');\n");
+    out.push_str("FILE_NAME('text.step','',(''),(''),'fonts','','');\n");
+    out.push_str("FILE_SCHEMA(('AUTOMOTIVE_DESIGN { 1 0 10303 214 1 1 1 1 }'));\n");
+    out.push_str("ENDSEC;\nDATA;\n");
+    out.push_str(&step.data);
+    out.push_str("ENDSEC;\nEND-ISO-10303-21;\n");
+    out
+}
+
+/// The DATA section being written, numbering its entities in order
+#[derive(Default)]
+struct Step {
+    data: String,
+    entities: usize,
+}
+
+impl Step {
+    /// Appends the entity, returning its reference
+    fn entity(&mut self, entity: String) -> String {
+        self.entities += 1;
+        writeln!(self.data, "#{}={entity};", self.entities)
+            .expect("Writing to a String cannot fail");
+        format!("#{}", self.entities)
+    }
+
+    /// The geometric context of the representation: three dimensional, in millimetres
+    fn context(&mut self) -> String {
+        let length =
+            self.entity("(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT(.MILLI.,.METRE.))".to_string());
+        let angle = self.entity("(NAMED_UNIT(*)PLANE_ANGLE_UNIT()SI_UNIT($,.RADIAN.))".to_string());
+        let solid_angle =
+            self.entity("(NAMED_UNIT(*)SI_UNIT($,.STERADIAN.)SOLID_ANGLE_UNIT())".to_string());
+        let uncertainty = self.entity(format!(
+            "UNCERTAINTY_MEASURE_WITH_UNIT(LENGTH_MEASURE(1.E-07),{length},'distance_accuracy_value','')"
+        ));
+        self.entity(format!(
+            "(GEOMETRIC_REPRESENTATION_CONTEXT(3)GLOBAL_UNCERTAINTY_ASSIGNED_CONTEXT(({uncertainty}))\
+             GLOBAL_UNIT_ASSIGNED_CONTEXT(({length},{angle},{solid_angle}))REPRESENTATION_CONTEXT('',''))"
+        ))
+    }
+
+    /// The product the wireframe is the shape of, as importers expect every representation to
+    /// belong to one
+    fn product(&mut self) -> String {
+        let application = self.entity("APPLICATION_CONTEXT('automotive design')".to_string());
+        self.entity(format!(
+            "APPLICATION_PROTOCOL_DEFINITION('international standard','automotive_design',2000,{application})"
+        ));
+        let product_context =
+            self.entity(format!("PRODUCT_CONTEXT('',{application},'mechanical')"));
+        let definition_context = self.entity(format!(
+            "PRODUCT_DEFINITION_CONTEXT('part definition',{application},'design')"
+        ));
+        let product = self.entity(format!("PRODUCT('Text','Text','',({product_context}))"));
+        let formation = self.entity(format!("PRODUCT_DEFINITION_FORMATION('','',{product})"));
+        let definition = self.entity(format!(
+            "PRODUCT_DEFINITION('design','',{formation},{definition_context})"
+        ));
+        self.entity(format!("PRODUCT_DEFINITION_SHAPE('','',{definition})"))
+    }
+
+    /// The primitive as a single Bezier segment, a clamped B-spline without interior knots
+    fn curve(&mut self, primitive: &Primitive) -> String {
+        let poles = primitive.points();
+        let poles: Vec<String> = poles.iter().map(|point| self.point(point)).collect();
+        let degree = poles.len() - 1;
+        self.entity(format!(
+            "B_SPLINE_CURVE_WITH_KNOTS('',{degree},({}),.UNSPECIFIED.,.F.,.F.,({},{}),(0.,1.),.UNSPECIFIED.)",
+            poles.join(","),
+            degree + 1,
+            degree + 1
+        ))
+    }
+
+    fn point(&mut self, point: &Point) -> String {
+        self.entity(format!(
+            "CARTESIAN_POINT('',({},{},0.))",
+            step_real(point.0),
+            step_real(point.1)
+        ))
+    }
+}
+
+/// Formats the number as a STEP real, which always has a decimal point
+pub(crate) fn step_real(value: f32) -> String {
+    // Adding zero turns a negated 0.0 into 0.0 so "-0." never shows up in the output
+    let value = format!("{}", value + 0.0);
+    if value.contains('.') {
+        value
+    } else {
+        value + "."
+    }
+}
+
+/// Quotes the text as a STEP string. Apostrophes and backslashes are doubled, and characters
+/// outside printable ASCII are written as their UTF-16 or UTF-32 code
+fn step_string(text: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in text.chars() {
+        match c {
+            '\'' => quoted.push_str("''"),
+            '\\' => quoted.push_str("\\\\"),
+            ' '..='~' => quoted.push(c),
+            c if u32::from(c) <= 0xffff => write!(quoted, "\\X2\\{:04X}\\X0\\", u32::from(c))
+                .expect("Writing to a String cannot fail"),
+            c => write!(quoted, "\\X4\\{:08X}\\X0\\", u32::from(c))
+                .expect("Writing to a String cannot fail"),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
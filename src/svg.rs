@@ -0,0 +1,74 @@
+use crate::{Point, Primitive, Shape};
+use std::fmt::Write;
+
+/// Renders the shapes as a standalone SVG document, one `<path>` per shape.
+///
+/// SVG grows its Y axis downwards, so every Y coordinate is negated to keep the text upright.
+/// Stroke shapes are drawn as hairlines instead of being filled.
+pub fn to_svg(shapes: &[Shape], min_point: &Point, max_point: &Point) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        min_point.0,
+        -max_point.1,
+        max_point.0 - min_point.0,
+        max_point.1 - min_point.1
+    )
+    .expect("Writing to a String cannot fail");
+
+    for shape in shapes.iter().filter(|shape| !shape.primitives.is_empty()) {
+        let paint = if shape.stroke {
+            r#"fill="none" stroke="black" stroke-width="1" vector-effect="non-scaling-stroke""#
+        } else {
+            r#"fill="black" fill-rule="evenodd""#
+        };
+        writeln!(svg, r#"  <path d="{}" {paint}/>"#, path_data(shape))
+            .expect("Writing to a String cannot fail");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn path_data(shape: &Shape) -> String {
+    let mut d = vec![];
+    let mut contour_start: Option<&Point> = None;
+    let mut last_point: Option<&Point> = None;
+
+    for primitive in shape.primitives.iter() {
+        let start = primitive.start();
+        if last_point != Some(start) {
+            if last_point.is_some() && last_point == contour_start {
+                d.push("Z".to_string());
+            }
+            d.push(format!("M {}", svg_point(start)));
+            contour_start = Some(start);
+        }
+
+        d.push(match primitive {
+            // The next primitive starts a new contour, or there is none, so Z is added then
+            Primitive::Close(..) => {
+                last_point = Some(primitive.end());
+                continue;
+            }
+            Primitive::Line(_, p) => format!("L {}", svg_point(p)),
+            Primitive::Quadratic(_, c, p) => format!("Q {} {}", svg_point(c), svg_point(p)),
+            Primitive::Bezier(_, c0, c1, p) => {
+                format!("C {} {} {}", svg_point(c0), svg_point(c1), svg_point(p))
+            }
+        });
+        last_point = Some(primitive.end());
+    }
+
+    if last_point.is_some() && last_point == contour_start {
+        d.push("Z".to_string());
+    }
+
+    d.join(" ")
+}
+
+fn svg_point(point: &Point) -> String {
+    // Adding zero turns a negated 0.0 into 0.0 so "-0" never shows up in the output
+    format!("{},{}", point.0, -point.1 + 0.0)
+}
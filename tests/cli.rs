@@ -0,0 +1,674 @@
+mod common;
+
+use common::{render, run, serve};
+use serde_json::Value;
+
+fn shapes(output: &Value) -> &[Value] {
+    output["shapes"].as_array().expect("a shapes array")
+}
+
+fn point(value: &Value) -> (f64, f64) {
+    (value[0].as_f64().unwrap(), value[1].as_f64().unwrap())
+}
+
+#[test]
+fn renders_known_geometry() {
+    let output = render("I", &["--no-normalize"]);
+    assert_eq!(point(&output["bbox"]["min"]), (1.281, -14.0));
+    assert_eq!(point(&output["bbox"]["max"]), (7.109, -4.343));
+
+    let shapes = shapes(&output);
+    assert_eq!(shapes.len(), 1);
+    let primitives = shapes[0]["primitives"].as_array().unwrap();
+    assert_eq!(primitives.len(), 12);
+    assert_eq!(
+        primitives[0]["Line"],
+        serde_json::json!([[7.109, -5.578], [5.015, -5.578]])
+    );
+    assert_eq!(shapes[0]["char"], "I");
+    assert_eq!(shapes[0]["closed"], true);
+}
+
+#[test]
+fn normalizes_into_unit_box() {
+    let output = render("Hello", &[]);
+    let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+    assert_eq!(min, (0.0, 0.0));
+    assert_eq!(max.0, 1.0);
+    assert!(max.1 < 1.0);
+}
+
+#[test]
+fn splits_glyphs_into_contours() {
+    let output = render("oB", &[]);
+    let counts = |character: &str| {
+        shapes(&output)
+            .iter()
+            .filter(|shape| shape["char"] == character)
+            .count()
+    };
+    assert_eq!(counts("o"), 2);
+    assert_eq!(counts("B"), 3);
+}
+
+#[test]
+fn rejects_empty_input() {
+    let output = run(&["--", " "]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no renderable glyphs"));
+}
+
+#[test]
+fn rejects_input_without_visible_characters() {
+    for text in ["", "\u{1}\u{7f}", "\u{200b}\u{200d}\u{2060}\u{feff}"] {
+        let output = run(&["--", text]);
+        assert_eq!(output.status.code(), Some(1), "{text:?} was accepted");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "error: no renderable glyphs in the input\n"
+        );
+    }
+}
+
+#[test]
+fn output_is_reproducible() {
+    let args = ["--stable-order", "--", "Reproducible"];
+    assert_eq!(run(&args).stdout, run(&args).stdout);
+}
+
+/// Lowest and highest Y coordinate of the given shapes
+fn vertical_extent<'a>(shapes: impl Iterator<Item = &'a Value>) -> (f64, f64) {
+    shapes
+        .flat_map(|shape| shape["primitives"].as_array().unwrap())
+        .flat_map(|primitive| primitive.as_object().unwrap().values())
+        .flat_map(|points| points.as_array().unwrap())
+        .map(|p| point(p).1)
+        .fold((f64::MAX, f64::MIN), |(min, max), y| {
+            (min.min(y), max.max(y))
+        })
+}
+
+#[test]
+fn stacks_combining_marks() {
+    // Fira Mono has a precomposed "ö" but no "ö" with an acute, so the acute is positioned as a
+    // mark on top of the diaeresis
+    let output = render("o\u{308}\u{301}", &["--normalize-unicode", "none"]);
+    let shapes = shapes(&output);
+    let glyph = |index: u64| shapes.iter().filter(move |shape| shape["index"] == index);
+    assert_eq!(glyph(0).count(), 4);
+    let (_, base_top) = vertical_extent(glyph(0));
+    let (mark_bottom, _) = vertical_extent(glyph(1));
+    assert!(
+        mark_bottom > base_top,
+        "the acute at {mark_bottom} overlaps the base, whose top is at {base_top}"
+    );
+}
+
+#[test]
+fn composed_and_decomposed_input_match() {
+    assert_eq!(render("\u{e9}", &[]), render("e\u{301}", &[]));
+}
+
+#[test]
+fn union_merges_overlapping_glyphs() {
+    let output = render("OO", &["--letter-spacing", "-3", "--union"]);
+    let shapes = shapes(&output);
+    assert_eq!(shapes.len(), 3);
+    assert!(shapes.iter().all(|shape| shape["index"] == 0));
+    assert_eq!(shapes[0]["orientation"], "ccw");
+    assert!(shapes[1..].iter().all(|hole| hole["orientation"] == "cw"));
+}
+
+#[test]
+fn groups_shapes_by_line() {
+    let output = render("I\nII", &["--group-by-line"]);
+    let lines = output["lines"].as_array().expect("a lines array");
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1]["line"], 1);
+    assert_eq!(lines[1]["shapes"].as_array().unwrap().len(), 2);
+    assert!(point(&lines[1]["baseline"]).1 < point(&lines[0]["baseline"]).1);
+}
+
+#[test]
+fn render_size_places_glyphs_between_pixels() {
+    // Fira Mono advances 600 of its 1000 units per em, 8.4 pixels at the default font size,
+    // which whole pixel positions would round down to 8
+    let output = render("II", &["--no-normalize", "--render-size", "1400"]);
+    let shapes = shapes(&output);
+    let start = |shape: &Value| point(&shape["primitives"][0]["Line"][0]).0;
+    let advance = start(&shapes[1]) - start(&shapes[0]);
+    assert!((advance - 8.4).abs() < 0.01, "advanced by {advance}");
+}
+
+#[test]
+fn scale_factor_doubles_extents() {
+    let extent = |output: &Value| {
+        let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+        (max.0 - min.0, max.1 - min.1)
+    };
+    let single = extent(&render("Hi", &["--no-normalize"]));
+    let double = extent(&render("Hi", &["--no-normalize", "--scale-factor", "2"]));
+    assert!((double.0 - 2.0 * single.0).abs() < 1.0);
+    assert!((double.1 - 2.0 * single.1).abs() < 1.0);
+    assert_eq!(
+        render("Hi", &["--no-normalize", "--dpi", "192"]),
+        render("Hi", &["--no-normalize", "--scale-factor", "2"])
+    );
+}
+
+#[test]
+fn fit_width_keeps_aspect_ratio() {
+    let size = |output: &Value| {
+        let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+        (max.0 - min.0, max.1 - min.1)
+    };
+    let (width, height) = size(&render("Fit\nwidth", &["--fit-width", "50"]));
+    let (unit_width, unit_height) = size(&render("Fit\nwidth", &[]));
+    assert_eq!(width, 50.0);
+    assert!((height - 50.0 * unit_height / unit_width).abs() < 0.01);
+}
+
+#[test]
+fn freecad_macro_lists_contours() {
+    let output = run(&["--format", "fcmacro", "--", "o"]);
+    assert!(output.status.success());
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("import Part"));
+    assert!(script.contains("    (\"o\", False, ["));
+    let contours: Vec<&str> = script
+        .lines()
+        .filter(|line| line.starts_with("        [("))
+        .collect();
+    assert_eq!(contours.len(), 2);
+    // Fira Mono is a TrueType font, so its curves are quadratic and have three poles
+    let poles: Vec<usize> = contours[0]
+        .split(")), ((")
+        .map(|edge| edge.matches("), (").count() + 1)
+        .collect();
+    assert!(poles.iter().all(|&count| count == 2 || count == 3));
+    assert!(poles.contains(&3));
+    assert!(script.contains("Part.BSplineCurve()"));
+}
+
+#[test]
+fn invert_encloses_glyphs_in_plate() {
+    let output = render("Stencil", &["--invert", "--bbox-pad", "0.05"]);
+    let shapes = shapes(&output);
+    let plate = shapes.last().unwrap();
+    assert_eq!(plate["char"], "");
+    assert_eq!(plate["orientation"], "cw");
+    assert!(plate["area"].as_f64().unwrap() < 0.0);
+    let corners: Vec<(f64, f64)> = plate["primitives"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|line| point(&line["Line"][0]))
+        .collect();
+    assert_eq!(corners.len(), 4);
+    let (min, max) = (corners[0], corners[2]);
+    assert_eq!(point(&output["bbox"]["min"]), min);
+    assert_eq!(point(&output["bbox"]["max"]), max);
+    let points = shapes[..shapes.len() - 1]
+        .iter()
+        .flat_map(|shape| shape["primitives"].as_array().unwrap())
+        .flat_map(|primitive| primitive.as_object().unwrap().values())
+        .flat_map(|points| points.as_array().unwrap())
+        .map(point);
+    for (x, y) in points {
+        assert!(x > min.0 && x < max.0 && y > min.1 && y < max.1);
+    }
+}
+
+#[test]
+fn overflowing_coordinates_fail_cleanly() {
+    let output = run(&["--no-normalize", "--scale", "1e38", "--", "I"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("infinite or NaN"));
+}
+
+#[test]
+fn glyph_boxes_cover_the_bounding_box() {
+    let output = render("Ag,", &["--glyph-bbox"]);
+    let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+    for shape in shapes(&output) {
+        let (glyph_min, glyph_max) = (point(&shape["bbox"]["min"]), point(&shape["bbox"]["max"]));
+        min = (min.0.min(glyph_min.0), min.1.min(glyph_min.1));
+        max = (max.0.max(glyph_max.0), max.1.max(glyph_max.1));
+    }
+    assert_eq!(min, point(&output["bbox"]["min"]));
+    assert_eq!(max, point(&output["bbox"]["max"]));
+}
+
+#[test]
+fn per_glyph_normalize_scales_each_glyph() {
+    let output = render("i.", &["--per-glyph-normalize", "--glyph-bbox"]);
+    for shape in shapes(&output) {
+        let (min, max) = (point(&shape["bbox"]["min"]), point(&shape["bbox"]["max"]));
+        assert_eq!(min, (0.0, 0.0));
+        assert_eq!(f64::max(max.0, max.1), 1.0);
+    }
+}
+
+#[test]
+fn vertical_text_stacks_glyphs() {
+    // The bundled font has no CJK glyphs, Latin capitals are stacked the same way
+    let output = render("ABC", &["--vertical", "--glyph-bbox"]);
+    let boxes: Vec<((f64, f64), (f64, f64))> = shapes(&output)
+        .iter()
+        .filter(|shape| shape["contour"] == 0)
+        .map(|shape| (point(&shape["bbox"]["min"]), point(&shape["bbox"]["max"])))
+        .collect();
+    assert_eq!(boxes.len(), 3);
+    for pair in boxes.windows(2) {
+        let ((upper_min, upper_max), (lower_min, lower_max)) = (pair[0], pair[1]);
+        assert!(lower_max.1 < upper_min.1, "{pair:?} are not stacked");
+        assert!(lower_min.0 < upper_max.0 && upper_min.0 < lower_max.0);
+    }
+}
+
+#[test]
+fn dry_run_prints_a_summary() {
+    let output = run(&[
+        "--dry-run",
+        "--target-size",
+        "50",
+        "--units",
+        "mm",
+        "--",
+        "Hi",
+    ]);
+    assert!(output.status.success());
+    let summary = String::from_utf8(output.stdout).unwrap();
+    assert!(summary.contains("shapes: 3\n"));
+    assert!(summary.contains("path length: "));
+    let output = run(&["--dry-run", "--strict", "--", "a\u{378}"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn server_answers_every_request() {
+    let dir = std::env::temp_dir().join(format!("fonts-serve-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (first, second) = (dir.join("first.json"), dir.join("second.json"));
+    let requests = format!(
+        "I\t{}\nI\\nII\t{}\n \t{}\nno tab\n",
+        first.display(),
+        second.display(),
+        dir.join("empty.json").display()
+    );
+    let output = serve(&requests);
+    assert!(output.status.success());
+    let responses = String::from_utf8(output.stdout).unwrap();
+    let responses: Vec<&str> = responses.lines().collect();
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0], format!("ok\t{}", first.display()));
+    assert!(responses[1].starts_with("ok\t"));
+    assert!(responses[2].starts_with("error\t"));
+    assert!(responses[3].starts_with("error\t"));
+
+    let output: Value = serde_json::from_slice(&std::fs::read(&second).unwrap()).unwrap();
+    assert_eq!(shapes(&output).len(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keep_close_marks_the_end_of_contours() {
+    let last = |text: &str, args: &[&str]| {
+        let output = render(text, args);
+        let primitives = shapes(&output)[0]["primitives"].as_array().unwrap().clone();
+        primitives.last().unwrap().clone()
+    };
+    // The outer contour of "o" draws its last curve back to its start, the close adds nothing
+    let close = &last("o", &["--keep-close"])["Close"];
+    assert_eq!(close[0], close[1]);
+    assert!(last("o", &[]).get("Quadratic").is_some());
+    // "I" relies on the close for its last edge, which is a line unless the close is kept
+    let close = &last("I", &["--keep-close"])["Close"];
+    assert_ne!(close[0], close[1]);
+    assert_eq!(last("I", &[])["Line"], *close);
+}
+
+#[test]
+fn min_contour_area_drops_small_contours() {
+    // The dot of the "i" is far smaller than its stem
+    let output = run(&["--min-contour-area", "0.05", "--", "i"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("dropped 1 contours"));
+    let output: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let all = render("i", &[]);
+    assert_eq!(shapes(&output).len(), shapes(&all).len() - 1);
+    for shape in shapes(&output) {
+        assert!(shape["area"].as_f64().unwrap().abs() >= 0.05);
+    }
+}
+
+#[test]
+fn center_origin_centers_the_bounding_box() {
+    for args in [
+        &["--center-origin"][..],
+        &["--center-origin", "--rotate", "30"],
+    ] {
+        let output = render("Centered", args);
+        let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+        assert!((min.0 + max.0).abs() < 0.002, "{min:?} to {max:?}");
+        assert!((min.1 + max.1).abs() < 0.002, "{min:?} to {max:?}");
+    }
+    assert_eq!(
+        render("Centered", &["--center-origin"]),
+        render("Centered", &["--origin", "center"])
+    );
+}
+
+#[test]
+fn empty_font_db_dir_warns() {
+    let dir = std::env::temp_dir().join(format!("fonts-empty-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = run(&["--font-db-dir", dir.to_str().unwrap(), "--", "I"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no fonts found"));
+
+    let output = run(&["--font-db-dir", "does/not/exist", "--", "I"]);
+    assert!(!output.status.success());
+}
+
+#[cfg(feature = "step")]
+#[test]
+fn step_lists_a_curve_per_contour() {
+    let output = run(&["--format", "step", "--", "o"]);
+    assert!(output.status.success());
+    let step = String::from_utf8(output.stdout).unwrap();
+    assert!(step.starts_with("ISO-10303-21;\n"));
+    assert!(step.ends_with("END-ISO-10303-21;\n"));
+    assert_eq!(step.matches("=COMPOSITE_CURVE(").count(), 2);
+    // Fira Mono is a TrueType font, so its curves are quadratic B-splines
+    assert!(step.contains("B_SPLINE_CURVE_WITH_KNOTS('',2,"));
+    assert!(step.contains("GEOMETRIC_CURVE_SET('o',"));
+}
+
+#[cfg(feature = "step")]
+#[test]
+fn iges_lists_a_curve_per_contour() {
+    let output = run(&["--format", "iges", "--", "o"]);
+    assert!(output.status.success());
+    let iges = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = iges.lines().collect();
+    assert!(lines.iter().all(|line| line.len() == 80));
+    let sections: String = lines.iter().map(|line| &line[72..73]).collect();
+    assert!(sections.starts_with("SG") && sections.ends_with("PT"));
+    let count = |section: &str| sections.matches(section).count();
+    let terminate = lines.last().unwrap();
+    assert_eq!(
+        &terminate[..32],
+        format!(
+            "S{:>7}G{:>7}D{:>7}P{:>7}",
+            count("S"),
+            count("G"),
+            count("D"),
+            count("P")
+        )
+    );
+    // Every entity takes two directory lines, the first starting with its type
+    let entities = |kind: &str| {
+        lines
+            .iter()
+            .filter(|line| &line[72..73] == "D" && line[..8].trim() == kind)
+            .count()
+            / 2
+    };
+    assert_eq!(entities("102"), 2);
+    assert_eq!(entities("402"), 1);
+    // Fira Mono is a TrueType font, so its curves are quadratic B-splines
+    assert!(lines.iter().any(|line| line.starts_with("126,2,2,")));
+}
+
+#[test]
+fn stacks_lines_below_each_other() {
+    let gap = |args: &[&str]| {
+        let output = render("I\nI", &[&["--no-normalize"], args].concat());
+        let shapes = shapes(&output);
+        let line = |line: u64| shapes.iter().filter(move |shape| shape["line"] == line);
+        let (first_bottom, _) = vertical_extent(line(0));
+        let (_, second_top) = vertical_extent(line(1));
+        // The bounding box spans both lines
+        let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+        assert_eq!((min.1, max.1), vertical_extent(shapes.iter()));
+        first_bottom - second_top
+    };
+    let single = gap(&[]);
+    assert!(
+        single > 0.0,
+        "the second line overlaps the first by {single}"
+    );
+    let wider = gap(&["--line-gap", "10"]);
+    assert!(
+        (wider - single - 10.0).abs() < 0.01,
+        "the gap grew from {single} to {wider}"
+    );
+}
+
+#[test]
+fn normalizing_keeps_the_aspect_ratio() {
+    let ratio = |output: &Value| {
+        let (min, max) = (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+        (max.0 - min.0) / (max.1 - min.1)
+    };
+    let unit = ratio(&render("WWWWWW", &[]));
+    let original = ratio(&render("WWWWWW", &["--no-normalize"]));
+    assert!(unit > 1.0);
+    assert!(
+        (unit - original).abs() < 0.01 * original,
+        "{unit} != {original}"
+    );
+}
+
+#[test]
+fn flip_y_turns_glyphs_upside_down() {
+    let heights = |args: &[&str]| -> Vec<f64> {
+        let output = render("T", &[&["--no-normalize"], args].concat());
+        shapes(&output)[0]["primitives"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|primitive| primitive.as_object().unwrap().values())
+            .flat_map(|points| points.as_array().unwrap())
+            .map(|p| point(p).1)
+            .collect()
+    };
+    let (upright, flipped) = (heights(&[]), heights(&["--flip-y"]));
+    assert_eq!(upright.len(), flipped.len());
+    let highest = |ys: &[f64]| {
+        (0..ys.len())
+            .max_by(|&a, &b| ys[a].total_cmp(&ys[b]))
+            .unwrap()
+    };
+    let lowest = |ys: &[f64]| {
+        (0..ys.len())
+            .min_by(|&a, &b| ys[a].total_cmp(&ys[b]))
+            .unwrap()
+    };
+    // The top of the bar ends up below the foot of the stem
+    assert!(flipped[highest(&upright)] < flipped[lowest(&upright)]);
+    assert_eq!(upright[highest(&upright)], upright[lowest(&flipped)]);
+    assert_eq!(upright[lowest(&upright)], upright[highest(&flipped)]);
+}
+
+#[test]
+fn offset_shifts_the_bounding_box() {
+    let bbox = |output: &Value| (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+    let (min, max) = bbox(&render("Hi", &[]));
+    let (shifted_min, shifted_max) = bbox(&render("Hi", &["--offset", "3", "-2"]));
+    assert_eq!(shifted_min, (min.0 + 3.0, min.1 - 2.0));
+    assert!((shifted_max.0 - max.0 - 3.0).abs() < 1e-3);
+    assert!((shifted_max.1 - max.1 + 2.0).abs() < 1e-3);
+}
+
+#[test]
+fn tabs_line_up_columns() {
+    let output = render("a\tb\naaaa\tb", &["--no-normalize"]);
+    let left = |line: u64| {
+        shapes(&output)
+            .iter()
+            .filter(|shape| shape["char"] == "b" && shape["line"] == line)
+            .flat_map(|shape| shape["primitives"].as_array().unwrap())
+            .flat_map(|primitive| primitive.as_object().unwrap().values())
+            .flat_map(|points| points.as_array().unwrap())
+            .map(|p| point(p).0)
+            .fold(f64::MAX, f64::min)
+    };
+    assert!(left(0) < f64::MAX);
+    assert!(
+        (left(0) - left(1)).abs() < 0.01,
+        "{} != {}",
+        left(0),
+        left(1)
+    );
+}
+
+#[test]
+fn center_alignment_offsets_the_shorter_line() {
+    let starts = |align: &str| {
+        let output = render("I\nIII", &["--no-normalize", "--align", align]);
+        let start = |line: u64| {
+            shapes(&output)
+                .iter()
+                .filter(|shape| shape["line"] == line)
+                .map(|shape| point(&shape["primitives"][0]["Line"][0]).0)
+                .fold(f64::MAX, f64::min)
+        };
+        (start(0), start(1))
+    };
+    let (short, long) = starts("left");
+    assert!((short - long).abs() < 0.01);
+    let (short, long) = starts("center");
+    // Moved in by half of the two glyphs it is shorter by, about one advance
+    assert!(short - long > 7.0, "{short} is not centered over {long}");
+}
+
+#[test]
+fn nesting_puts_the_counter_of_o_into_its_outline() {
+    let output = render("O", &["--nest"]);
+    let regions = output["regions"].as_array().expect("a regions array");
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0]["outer"]["char"], "O");
+    assert_eq!(regions[0]["holes"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn shear_slants_vertical_stems() {
+    let lines = |args: &[&str]| -> Vec<((f64, f64), (f64, f64))> {
+        let output = render("I", &[&["--no-normalize"], args].concat());
+        shapes(&output)[0]["primitives"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|primitive| {
+                let line = &primitive["Line"];
+                (point(&line[0]), point(&line[1]))
+            })
+            .collect()
+    };
+    let (upright, sheared) = (lines(&[]), lines(&["--shear", "10"]));
+    let stem = upright
+        .iter()
+        .position(|(start, end)| start.0 == end.0 && (end.1 - start.1).abs() > 5.0)
+        .expect("a vertical stem");
+    let (start, end) = sheared[stem];
+    let expected = 10f64.to_radians().tan() * (end.1 - start.1);
+    assert!(
+        (end.0 - start.0 - expected).abs() < 0.01,
+        "the stem leans by {} instead of {expected}",
+        end.0 - start.0
+    );
+}
+
+#[test]
+fn bbox_pad_insets_the_geometry_evenly() {
+    let bbox = |output: &Value| (point(&output["bbox"]["min"]), point(&output["bbox"]["max"]));
+    let (_, max) = bbox(&render("Hello", &[]));
+    let (padded_min, padded_max) = bbox(&render("Hello", &["--bbox-pad", "0.1"]));
+    assert_eq!(padded_min, (0.1, 0.1));
+    assert!((1.0 - padded_max.0 - padded_min.0).abs() < 1e-9);
+    assert!((padded_max.1 - padded_min.1 - 0.8 * max.1).abs() < 0.01);
+}
+
+#[test]
+fn nonzero_fill_rule_opposes_holes_to_their_outline() {
+    let orientations = |args: &[&str]| -> Vec<Value> {
+        shapes(&render("O", args))
+            .iter()
+            .map(|shape| shape["orientation"].clone())
+            .collect()
+    };
+    // Fira Mono winds its outer contours clockwise, the nonzero rule wants them counterclockwise
+    assert_eq!(orientations(&[]), ["cw", "ccw"]);
+    assert_eq!(orientations(&["--fill-rule", "nonzero"]), ["ccw", "cw"]);
+}
+
+#[test]
+fn font_index_needs_a_single_font_file() {
+    let fallback = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/DejaVuSans.ttf");
+    let output = run(&["--font-file", fallback, "--font-index", "0", "--", "a"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("--font-index cannot be used with more than one --font-file"));
+    assert!(run(&["--font-index", "0", "--", "a"]).status.success());
+}
+
+#[test]
+fn source_ranges_follow_every_paragraph_separator() {
+    for text in ["ab\r\ncd", "ab\rcd", "ab\u{2029}cd"] {
+        for direction in ["auto", "ltr", "rtl"] {
+            let output = render(text, &["--base-direction", direction]);
+            let shapes = shapes(&output);
+            assert_eq!(shapes.len(), 7);
+            for shape in shapes {
+                let range = &shape["source_range"];
+                let (start, end) = (range[0].as_u64().unwrap(), range[1].as_u64().unwrap());
+                assert_eq!(
+                    text[start as usize..end as usize],
+                    shape["char"],
+                    "in {text:?} with --base-direction {direction}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn schema_selects_the_structure() {
+    assert_eq!(render("I", &[])["version"], 3);
+    assert!(render("I", &["--schema", "1"]).is_array());
+
+    let older = render("I", &["--schema", "2"]);
+    assert_eq!(older["version"], 2);
+    for field in ["line", "source_range"] {
+        assert!(shapes(&older)[0].get(field).is_none(), "{field} is written");
+    }
+    let nested = render("o", &["--schema", "2", "--nest"]);
+    assert!(nested["regions"][0]["outer"].get("line").is_none());
+
+    let output = run(&["--schema", "2", "--length", "--", "I"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--length needs --schema 3"));
+    assert!(!run(&["--schema", "4", "--", "I"]).status.success());
+}
+
+#[test]
+fn fit_width_ignores_the_padding() {
+    let output = render("Fit\nwidth", &["--fit-width", "100", "--bbox-pad", "0.1"]);
+    let mut extents = [(f64::MAX, f64::MIN); 2];
+    for shape in shapes(&output) {
+        let extent = &mut extents[shape["line"].as_u64().unwrap() as usize];
+        for primitive in shape["primitives"].as_array().unwrap() {
+            let (_, points) = primitive.as_object().unwrap().iter().next().unwrap();
+            for (x, _) in points.as_array().unwrap().iter().map(point) {
+                *extent = (extent.0.min(x), extent.1.max(x));
+            }
+        }
+    }
+    let widest = extents
+        .iter()
+        .map(|(min, max)| max - min)
+        .fold(0.0, f64::max);
+    assert!((widest - 100.0).abs() < 0.01, "the widest line is {widest}");
+}
@@ -0,0 +1,54 @@
+//! Helpers shared by the integration tests, which run the binary against the bundled Fira Mono
+//! font so the geometry does not depend on the fonts installed on the machine. Only characters
+//! covered by the font are used, otherwise fallback fonts would take part again.
+
+// Each test crate uses its own subset of the helpers
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const FONT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/FiraMono-Medium.ttf"
+);
+
+/// Runs the binary with the bundled font, writing the output to stdout
+pub fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fonts"))
+        .args(["--font-file", FONT])
+        .args(args)
+        .arg("-")
+        .output()
+        .expect("the binary to run")
+}
+
+/// Renders `text` with the extra `args` and parses the JSON output
+pub fn render(text: &str, args: &[&str]) -> Value {
+    let output = run(&[args, &["--", text]].concat());
+    assert!(
+        output.status.success(),
+        "rendering {text:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("valid JSON output")
+}
+
+/// Runs the binary in server mode with the bundled font, answering the given requests
+pub fn serve(requests: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fonts"))
+        .args(["--font-file", FONT, "--serve"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary to run");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(requests.as_bytes())
+        .expect("the requests to be written");
+    child.wait_with_output().expect("the binary to exit")
+}
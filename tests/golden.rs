@@ -0,0 +1,76 @@
+//! Compares the JSON output for a few strings against the files in tests/golden. Run with
+//! UPDATE_GOLDEN=1 to regenerate them after an intended change.
+
+mod common;
+
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Largest difference tolerated between two numbers, a tenth of the last digit kept by default
+const TOLERANCE: f64 = 1e-4;
+
+fn check(name: &str, text: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.json"));
+    let actual = common::render(text, &["--pretty"]);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let json = serde_json::to_string_pretty(&actual).unwrap();
+        std::fs::write(&path, json + "\n").expect("the golden file to be writable");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "{} is missing, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    let expected: Value = serde_json::from_str(&expected).expect("a valid golden file");
+    if let Err(at) = compare(&expected, &actual, String::new()) {
+        panic!("{name} differs from its golden file at {at}, run with UPDATE_GOLDEN=1 if intended");
+    }
+}
+
+/// Compares both values structurally, with numbers within [`TOLERANCE`], returning the path of
+/// the first difference
+fn compare(expected: &Value, actual: &Value, at: String) -> Result<(), String> {
+    match (expected, actual) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+            if (a - b).abs() <= TOLERANCE {
+                Ok(())
+            } else {
+                Err(format!("{at}: {a} != {b}"))
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .try_for_each(|(i, (a, b))| compare(a, b, format!("{at}[{i}]"))),
+        (Value::Object(a), Value::Object(b)) if a.len() == b.len() => {
+            a.iter().try_for_each(|(key, a)| {
+                let b = b.get(key).ok_or_else(|| format!("{at}.{key}: missing"))?;
+                compare(a, b, format!("{at}.{key}"))
+            })
+        }
+        (a, b) if a == b => Ok(()),
+        (a, b) => Err(format!("{at}: {a} != {b}")),
+    }
+}
+
+#[test]
+fn golden_a() {
+    check("A", "A");
+}
+
+#[test]
+fn golden_oo() {
+    check("Oo", "Oo");
+}
+
+#[test]
+fn golden_fi() {
+    check("fi", "fi");
+}
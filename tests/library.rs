@@ -0,0 +1,546 @@
+//! Drives the library directly, with a font database holding only the bundled font so no system
+//! font can take part.
+
+use cosmic_text::{FontSystem, SwashCache};
+use fonts::{render_text, RenderOptions};
+
+fn font_system() -> FontSystem {
+    let mut db = fontdb::Database::new();
+    db.load_font_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/FiraMono-Medium.ttf"
+    ))
+    .expect("the bundled font to load");
+    FontSystem::new_with_locale_and_db("en-US".to_string(), db)
+}
+
+/// The Fira Mono database with DejaVu Sans added, which covers Hebrew
+fn with_dejavu_sans() -> FontSystem {
+    let mut font_system = font_system();
+    font_system
+        .db_mut()
+        .load_font_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/DejaVuSans.ttf"
+        ))
+        .expect("the bundled font to load");
+    font_system
+}
+
+/// Leftmost X coordinate of the shapes of `character` on `line`
+fn left_edge(shapes: &[fonts::Shape], character: &str, line: usize) -> f32 {
+    shapes
+        .iter()
+        .filter(|shape| shape.character == character && shape.line == line)
+        .flat_map(|shape| shape.primitives.iter())
+        .flat_map(fonts::Primitive::points)
+        .map(|point| point.0)
+        .fold(f32::MAX, f32::min)
+}
+
+/// A square of lines from the origin to (1, 1), running clockwise or counter-clockwise
+fn unit_square(clockwise: bool) -> fonts::Shape {
+    use fonts::{Point, Primitive, Shape};
+    let mut corners = [
+        Point(0.0, 0.0),
+        Point(1.0, 0.0),
+        Point(1.0, 1.0),
+        Point(0.0, 1.0),
+    ];
+    if clockwise {
+        corners.reverse();
+    }
+    let primitives = corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(start, end)| Primitive::Line(start.clone(), end.clone()))
+        .collect();
+    Shape::new(primitives, String::new(), 0, 0)
+}
+
+#[test]
+fn default_options_normalize() {
+    let rendering = render_text(
+        "Hi",
+        &RenderOptions::default(),
+        &mut font_system(),
+        &mut SwashCache::new(),
+    )
+    .unwrap();
+    let (min, max) = fonts::bounding_box(&rendering.shapes).unwrap();
+    assert_eq!(min, fonts::Point(0.0, 0.0));
+    assert_eq!(max.0, 1.0);
+}
+
+#[test]
+fn builder_sets_options() {
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .font_size(20.0)
+        .normalize(false)
+        .flatten(0.1)
+        .precision(1);
+    let rendering = render_text("o", &opts, &mut font_system(), &mut SwashCache::new()).unwrap();
+
+    assert_eq!(rendering.shapes.len(), 2);
+    for shape in rendering.shapes.iter() {
+        assert!(shape
+            .primitives
+            .iter()
+            .all(|primitive| matches!(primitive, fonts::Primitive::Line(..))));
+    }
+    // Twenty pixel text is not squeezed into the unit box
+    let (min, max) = fonts::bounding_box(&rendering.shapes).unwrap();
+    assert!(max.0 - min.0 > 5.0);
+}
+
+#[test]
+fn unit_square_has_length_four() {
+    assert!((unit_square(false).length() - 4.0).abs() < 1e-6);
+}
+
+#[test]
+fn elevated_quadratics_trace_the_same_curve() {
+    use fonts::{Point, Primitive};
+    let quadratic = Primitive::Quadratic(Point(0.0, 0.0), Point(1.0, 2.0), Point(3.0, -1.0));
+    let cubic = quadratic.clone().into_cubic();
+    assert!(matches!(cubic, Primitive::Bezier(..)));
+    for (a, b) in quadratic.sample(16).iter().zip(cubic.sample(16).iter()) {
+        assert!((a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn shapes_survive_a_json_round_trip() {
+    let opts = RenderOptions::default().font_family("Fira Mono");
+    let shapes = render_text("Hi", &opts, &mut font_system(), &mut SwashCache::new())
+        .unwrap()
+        .shapes;
+    let json = serde_json::to_string(&shapes).unwrap();
+    let parsed: Vec<fonts::Shape> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, shapes);
+}
+
+#[test]
+fn mirroring_twice_restores_the_geometry() {
+    use fonts::Point;
+    let render = |opts: RenderOptions| {
+        render_text("Fe", &opts, &mut font_system(), &mut SwashCache::new())
+            .unwrap()
+            .shapes
+    };
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .normalize(false);
+    let original = render(opts.clone());
+    let mirrored = render(opts.mirror_x(true).mirror_y(true));
+    assert_ne!(mirrored[0].primitives, original[0].primitives);
+    for (shape, original) in mirrored.into_iter().zip(original.iter()) {
+        let restored = shape.map_points(|p| Point(-p.0, -p.1));
+        assert_eq!(restored.primitives, original.primitives);
+    }
+}
+
+#[test]
+fn zero_length_lines_are_dropped() {
+    use fonts::{clean::clean_shape, Point, Primitive, Shape};
+    let primitives = vec![
+        Primitive::Line(Point(0.0, 0.0), Point(1.0, 0.0)),
+        Primitive::Line(Point(1.0, 0.0), Point(1.0, 0.0)),
+        Primitive::Line(Point(1.0, 0.0), Point(0.0, 1.0)),
+        Primitive::Line(Point(0.0, 1.0), Point(0.0, 0.0)),
+    ];
+    let shape = clean_shape(Shape::new(primitives, String::new(), 0, 0), 1e-6);
+    assert_eq!(shape.primitives.len(), 3);
+    assert!(shape
+        .primitives
+        .iter()
+        .all(|primitive| primitive.length() > 0.0));
+}
+
+#[test]
+fn square_winding_sets_the_sign_of_the_area() {
+    assert!((unit_square(false).signed_area() - 1.0).abs() < 1e-6);
+    assert!((unit_square(true).signed_area() + 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn curve_length_is_integrated() {
+    use fonts::{Point, Primitive};
+    // A straight quadratic, whose length is the distance between its end points
+    let straight = Primitive::Quadratic(Point(0.0, 0.0), Point(1.0, 1.0), Point(3.0, 3.0));
+    assert!((straight.length() - 18f32.sqrt()).abs() < 1e-4);
+    // The usual cubic approximation of a quarter of the unit circle
+    let k = 0.552_284_8;
+    let arc = Primitive::Bezier(
+        Point(1.0, 0.0),
+        Point(1.0, k),
+        Point(k, 1.0),
+        Point(0.0, 1.0),
+    );
+    assert!((arc.length() - std::f32::consts::FRAC_PI_2).abs() < 1e-3);
+}
+
+#[test]
+fn falls_back_per_character() {
+    let mut font_system = font_system();
+    font_system
+        .db_mut()
+        .load_font_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/DejaVuSansMono.ttf"
+        ))
+        .expect("the bundled fallback font to load");
+    // Fira Mono has no Georgian, which DejaVu Sans Mono covers
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .fallback_families(vec!["DejaVu Sans Mono".to_string()])
+        .strict(true);
+    let rendering = render_text("Aა", &opts, &mut font_system, &mut SwashCache::new()).unwrap();
+    for character in ["A", "ა"] {
+        assert!(rendering
+            .shapes
+            .iter()
+            .any(|shape| shape.character == character && !shape.primitives.is_empty()));
+    }
+}
+
+#[test]
+fn nan_coordinates_are_rejected() {
+    use fonts::{Point, Primitive, Shape};
+    let shape = Shape::new(
+        vec![
+            Primitive::Line(Point(0.0, 0.0), Point(f32::NAN, 1.0)),
+            Primitive::Line(Point(f32::NAN, 1.0), Point(0.0, 0.0)),
+        ],
+        "x".to_string(),
+        0,
+        0,
+    );
+    // Ordering the coordinates must not panic
+    let (min, _) = shape.get_bb().unwrap();
+    assert_eq!(min.1, 0.0);
+    let shapes = [shape];
+    assert!(fonts::bounding_box(&shapes).is_some());
+    assert!(matches!(
+        fonts::check_finite(&shapes),
+        Err(fonts::Error::NonFiniteGeometry(character)) if character == "x"
+    ));
+}
+
+#[test]
+fn empty_shapes_have_no_bounding_box() {
+    let shape = fonts::Shape::new(vec![], " ".to_string(), 0, 0);
+    assert!(shape.get_bb().is_none());
+    assert!(fonts::bounding_box(&[shape]).is_none());
+}
+
+#[test]
+fn reversing_flips_the_winding() {
+    let opts = RenderOptions::default().font_family("Fira Mono");
+    let shapes = render_text("o", &opts, &mut font_system(), &mut SwashCache::new())
+        .unwrap()
+        .shapes;
+    for shape in shapes {
+        let reversed = shape.clone().reverse();
+        let (area, reversed_area) = (shape.signed_area(), reversed.signed_area());
+        assert!(area != 0.0 && (reversed_area + area).abs() < 1e-6);
+        assert_eq!(reversed.reverse().primitives, shape.primitives);
+    }
+}
+
+#[test]
+fn nearly_straight_curves_become_lines() {
+    use fonts::{flatten::straighten_shape, Point, Primitive, Shape};
+    let primitives = vec![
+        Primitive::Quadratic(Point(0.0, 0.0), Point(1.0, 0.0004), Point(2.0, 0.0)),
+        Primitive::Quadratic(Point(2.0, 0.0), Point(3.0, 0.5), Point(4.0, 0.0)),
+    ];
+    let shape = straighten_shape(Shape::new(primitives, String::new(), 0, 0), 0.001);
+    assert_eq!(
+        shape.primitives[0],
+        Primitive::Line(Point(0.0, 0.0), Point(2.0, 0.0))
+    );
+    assert!(matches!(shape.primitives[1], Primitive::Quadratic(..)));
+}
+
+#[test]
+fn ligatures_span_their_characters() {
+    let mut font_system = font_system();
+    font_system
+        .db_mut()
+        .load_font_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/DejaVuSans.ttf"
+        ))
+        .expect("the bundled font with ligatures to load");
+    // DejaVu Sans draws "fi" as a single glyph, unlike Fira Mono
+    let opts = RenderOptions::default().font_family("DejaVu Sans");
+    let rendering = render_text("xfi", &opts, &mut font_system, &mut SwashCache::new()).unwrap();
+    let ligature: Vec<_> = rendering
+        .shapes
+        .iter()
+        .filter(|shape| shape.index == 1)
+        .collect();
+    assert!(!ligature.is_empty());
+    for shape in ligature {
+        assert_eq!(shape.character, "fi");
+        assert_eq!(shape.source_range, Some((1, 3)));
+    }
+
+    // The diaeresis and the acute are glyphs of their own, positioned on the "o" they share a
+    // cluster with
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .normalize_unicode(None);
+    let text = "xo\u{308}\u{301}";
+    let rendering = render_text(text, &opts, &mut font_system, &mut SwashCache::new()).unwrap();
+    let glyphs: std::collections::BTreeSet<usize> = rendering
+        .shapes
+        .iter()
+        .filter(|shape| shape.source_range == Some((1, text.len())))
+        .map(|shape| shape.index)
+        .collect();
+    assert!(glyphs.len() > 1);
+}
+
+#[test]
+fn font_dirs_are_searched() {
+    let mut font_system =
+        FontSystem::new_with_locale_and_db("en-US".to_string(), fontdb::Database::new());
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    assert_eq!(fonts::load_font_dir(&mut font_system, dir).unwrap(), 3);
+    let opts = RenderOptions::default()
+        .font_family("DejaVu Sans Mono")
+        .strict(true);
+    let rendering = render_text("ა", &opts, &mut font_system, &mut SwashCache::new()).unwrap();
+    assert!(!rendering.shapes.is_empty());
+}
+
+#[test]
+fn dxf_writes_decimal_commas() {
+    use fonts::{dxf::to_dxf, Point, Primitive, Shape};
+    let shape = Shape::new(
+        vec![
+            Primitive::Line(Point(0.5, 0.0), Point(1.25, 2.0)),
+            Primitive::Line(Point(1.25, 2.0), Point(0.5, 0.0)),
+        ],
+        "x".to_string(),
+        0,
+        0,
+    );
+    let shapes = [shape];
+    let dxf = to_dxf(&shapes, true, None, true);
+    assert!(dxf.contains("\n10\n0,5\n20\n0\n11\n1,25\n21\n2\n"));
+    assert!(!dxf.contains("0.5"));
+    // Only the coordinates change, the rest of the document is the same
+    let point = to_dxf(&shapes, true, None, false);
+    assert_eq!(dxf.replace(',', "."), point);
+}
+
+#[test]
+fn ranges_keep_the_context_of_the_whole_text() {
+    let mut font_system = font_system();
+    font_system
+        .db_mut()
+        .load_font_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/DejaVuSans.ttf"
+        ))
+        .expect("the bundled font with kerning to load");
+    let mut swash_cache = SwashCache::new();
+    let mut render = |opts: &RenderOptions| {
+        render_text("AV", opts, &mut font_system, &mut swash_cache)
+            .unwrap()
+            .shapes
+    };
+    // DejaVu Sans kerns "AV", unlike Fira Mono
+    let opts = RenderOptions::default().font_family("DejaVu Sans");
+    let whole: Vec<_> = render(&opts)
+        .into_iter()
+        .filter(|shape| shape.character == "V")
+        .collect();
+    let ranged = render(&opts.clone().range(1..2));
+    assert_eq!(ranged, whole);
+    let unkerned: Vec<_> = render(&opts.kerning(false))
+        .into_iter()
+        .filter(|shape| shape.character == "V")
+        .collect();
+    assert_ne!(ranged[0].primitives, unkerned[0].primitives);
+}
+
+#[test]
+fn kerning_moves_v_closer_to_a() {
+    let mut font_system = with_dejavu_sans();
+    let mut swash_cache = SwashCache::new();
+    let opts = RenderOptions::default()
+        .font_family("DejaVu Sans")
+        .normalize(false);
+    let mut advance = |opts: &RenderOptions| {
+        let shapes = render_text("AV", opts, &mut font_system, &mut swash_cache)
+            .unwrap()
+            .shapes;
+        left_edge(&shapes, "V", 0) - left_edge(&shapes, "A", 0)
+    };
+    let kerned = advance(&opts);
+    let unkerned = advance(&opts.kerning(false));
+    // DejaVu Sans has a negative kerning pair for "AV"
+    assert!(kerned < unkerned, "{kerned} is not below {unkerned}");
+}
+
+#[test]
+fn warnings_are_returned_to_the_caller() {
+    let opts = RenderOptions::default()
+        .font_family("Fira Mono")
+        .target_size(fonts::TargetSize::Longest(10.0));
+    let rendering = render_text(
+        "a\u{378}",
+        &opts,
+        &mut font_system(),
+        &mut SwashCache::new(),
+    )
+    .unwrap();
+    assert!(matches!(
+        rendering.warnings.as_slice(),
+        [fonts::Warning::MissingGlyphs(_)]
+    ));
+    let (scale_x, scale_y) = rendering.target_scale.expect("a target scale");
+    assert!(scale_x == scale_y && scale_x > 1.0);
+}
+
+#[test]
+fn base_direction_applies_to_every_paragraph() {
+    let opts = RenderOptions::default()
+        .font_family("DejaVu Sans")
+        .normalize(false)
+        .base_direction(fonts::BaseDirection::RightToLeft);
+    // cosmic-text also starts a paragraph after a lone carriage return
+    let shapes = render_text(
+        "a\u{5d1}\ra\u{5d1}",
+        &opts,
+        &mut with_dejavu_sans(),
+        &mut SwashCache::new(),
+    )
+    .unwrap()
+    .shapes;
+    for line in 0..2 {
+        assert!(
+            left_edge(&shapes, "\u{5d1}", line) < left_edge(&shapes, "a", line),
+            "line {line} is laid out left to right"
+        );
+    }
+}
+
+#[test]
+fn letter_spacing_separates_right_to_left_text() {
+    let left_edges = |letter_spacing: f32| {
+        let opts = RenderOptions::default()
+            .font_family("DejaVu Sans")
+            .normalize(false)
+            .letter_spacing(letter_spacing);
+        let shapes = render_text(
+            "\u{5d0}\u{5d1}\u{5d2}",
+            &opts,
+            &mut with_dejavu_sans(),
+            &mut SwashCache::new(),
+        )
+        .unwrap()
+        .shapes;
+        ["\u{5d0}", "\u{5d1}", "\u{5d2}"].map(|character| left_edge(&shapes, character, 0))
+    };
+    let (tight, spaced) = (left_edges(0.0), left_edges(5.0));
+    // Each letter moves away from the one before it, which is to its right
+    for i in 1..3 {
+        let gap = (spaced[i - 1] - spaced[i]) - (tight[i - 1] - tight[i]);
+        assert!((gap - 5.0).abs() < 0.1, "letter {i} moved by {gap}");
+    }
+}
+
+/// Collects the entity instances `parameter` refers to
+#[cfg(feature = "step")]
+fn step_references(parameter: &ruststep::ast::Parameter, references: &mut Vec<u64>) {
+    use ruststep::ast::{Name, Parameter};
+    match parameter {
+        Parameter::Ref(Name::Entity(id)) => references.push(*id),
+        Parameter::List(parameters) => {
+            for parameter in parameters {
+                step_references(parameter, references);
+            }
+        }
+        Parameter::Typed { parameter, .. } => step_references(parameter, references),
+        _ => {}
+    }
+}
+
+#[cfg(feature = "step")]
+#[test]
+fn step_output_is_read_back() {
+    use ruststep::ast::{EntityInstance, Parameter, Record};
+    let opts = RenderOptions::default().font_family("Fira Mono");
+    let shapes = render_text("Bo", &opts, &mut font_system(), &mut SwashCache::new())
+        .unwrap()
+        .shapes;
+    let step = fonts::step::to_step(&shapes);
+    let exchange = ruststep::parser::parse(&step).expect("a valid ISO 10303-21 file");
+    assert!(exchange
+        .header
+        .iter()
+        .any(|record| record.name == "FILE_SCHEMA"));
+
+    let entities: Vec<(u64, Vec<&Record>)> = exchange
+        .data
+        .iter()
+        .flat_map(|section| section.entities.iter())
+        .map(|entity| match entity {
+            EntityInstance::Simple { id, record } => (*id, vec![record]),
+            EntityInstance::Complex { id, subsuper } => (*id, subsuper.0.iter().collect()),
+        })
+        .collect();
+    let ids: std::collections::HashSet<u64> = entities.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids.len(), entities.len());
+    let records = || entities.iter().flat_map(|(_, records)| records.iter());
+    for record in records() {
+        let mut references = vec![];
+        step_references(&record.parameter, &mut references);
+        assert!(
+            references.iter().all(|id| ids.contains(id)),
+            "{} refers to a missing entity",
+            record.name
+        );
+    }
+
+    let named = |name: &'static str| records().filter(move |record| record.name == name);
+    assert_eq!(named("COMPOSITE_CURVE").count(), shapes.len());
+    for spline in named("B_SPLINE_CURVE_WITH_KNOTS") {
+        let Parameter::List(parameters) = &spline.parameter else {
+            panic!("B_SPLINE_CURVE_WITH_KNOTS without parameters");
+        };
+        let (Parameter::Integer(degree), Parameter::List(poles)) = (&parameters[1], &parameters[2])
+        else {
+            panic!("B_SPLINE_CURVE_WITH_KNOTS without a degree and poles");
+        };
+        assert_eq!(poles.len() as i64, degree + 1);
+    }
+}
+
+#[test]
+fn letter_spacing_widens_left_to_right_text() {
+    let render = |text: &str, letter_spacing: f32| {
+        let opts = RenderOptions::default()
+            .font_family("Fira Mono")
+            .normalize(false)
+            .letter_spacing(letter_spacing);
+        render_text(text, &opts, &mut font_system(), &mut SwashCache::new())
+            .unwrap()
+            .shapes
+    };
+    // A single character has no neighbour to move away from
+    assert_eq!(render("H", 5.0), render("H", 0.0));
+
+    let width = |shapes: &[fonts::Shape]| {
+        let (min, max) = fonts::bounding_box(shapes).unwrap();
+        max.0 - min.0
+    };
+    let grown = width(&render("HHHH", 5.0)) - width(&render("HHHH", 0.0));
+    assert!((grown - 3.0 * 5.0).abs() < 0.01, "grew by {grown}");
+}